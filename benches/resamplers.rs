@@ -10,12 +10,15 @@ use rubato::interpolator_neon::NeonInterpolator;
 #[cfg(target_arch = "x86_64")]
 use rubato::interpolator_sse::SseInterpolator;
 
-use rubato::{FftFixedIn, InterpolationType, Resampler, SincFixedIn, WindowFunction};
+use rubato::{
+    FftFixedIn, HalfbandDecimator, HalfbandInterpolator, InterpolationParameters,
+    InterpolationType, Resampler, SincFixedIn, WindowFunction,
+};
 
 fn bench_fftfixedin(c: &mut Criterion) {
     let chunksize = 1024;
     let mut resampler = FftFixedIn::<f64>::new(44100, 192000, 1024, 2, 1);
-    let waveform = vec![vec![0.0 as f64; chunksize]; 1];
+    let waveform = vec![vec![0.0_f64; chunksize]; 1];
     c.bench_function("FftFixedIn f64", |b| {
         b.iter(|| resampler.process(&waveform).unwrap())
     });
@@ -24,12 +27,67 @@ fn bench_fftfixedin(c: &mut Criterion) {
 fn bench_fftfixedin_32(c: &mut Criterion) {
     let chunksize = 1024;
     let mut resampler = FftFixedIn::<f32>::new(44100, 192000, 1024, 2, 1);
-    let waveform = vec![vec![0.0 as f32; chunksize]; 1];
+    let waveform = vec![vec![0.0_f32; chunksize]; 1];
     c.bench_function("FftFixedIn f32", |b| {
         b.iter(|| resampler.process(&waveform).unwrap())
     });
 }
 
+fn bench_halfband_decimator(c: &mut Criterion) {
+    let chunksize = 1024;
+    let mut resampler =
+        HalfbandDecimator::<f64>::new(24, WindowFunction::BlackmanHarris2, chunksize, 1);
+    let waveform = vec![vec![0.0_f64; chunksize]; 1];
+    c.bench_function("HalfbandDecimator f64", |b| {
+        b.iter(|| resampler.process(&waveform).unwrap())
+    });
+}
+
+fn bench_halfband_interpolator(c: &mut Criterion) {
+    let chunksize = 1024;
+    let mut resampler =
+        HalfbandInterpolator::<f64>::new(24, WindowFunction::BlackmanHarris2, chunksize, 1);
+    let waveform = vec![vec![0.0_f64; chunksize]; 1];
+    c.bench_function("HalfbandInterpolator f64", |b| {
+        b.iter(|| resampler.process(&waveform).unwrap())
+    });
+}
+
+/// Resample a full block of channels, where the sinc interpolated points of
+/// cubic interpolation share the loads of the input and the sincs.
+/// Cubic interpolation should then cost about the same as nearest, which makes one point per output.
+macro_rules! bench_async_block {
+    ($ip:expr, $f:ident, $desc:literal) => {
+        fn $f(c: &mut Criterion) {
+            let chunksize = 1024;
+            let channels = 4;
+            let parameters = InterpolationParameters {
+                sinc_len: 256,
+                f_cutoff: 0.95,
+                interpolation: $ip,
+                oversampling_factor: 256,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let resample_ratio = 192000 as f64 / 44100 as f64;
+            let mut resampler =
+                SincFixedIn::<f32>::new(resample_ratio, parameters, chunksize, channels);
+            let waveform = vec![vec![0.0_f32; chunksize]; channels];
+            c.bench_function($desc, |b| b.iter(|| resampler.process(&waveform).unwrap()));
+        }
+    };
+}
+
+bench_async_block!(
+    InterpolationType::Cubic,
+    bench_async_block_cubic,
+    "async cubic   4 channels"
+);
+bench_async_block!(
+    InterpolationType::Nearest,
+    bench_async_block_nearest,
+    "async nearest 4 channels"
+);
+
 /// Helper to unwrap the constructed interpolator if appropriate.
 macro_rules! unwrap_helper {
     (infallible $var:ident) => {
@@ -45,7 +103,7 @@ macro_rules! bench_async_resampler {
         fn $f(c: &mut Criterion) {
             let chunksize = 1024;
             let sinc_len = 256;
-            let f_cutoff = 0.9473371669037001;
+            let f_cutoff = 0.947_337_15;
             let oversampling_factor = 256;
             let window = WindowFunction::BlackmanHarris2;
             let resample_ratio = 192000 as f64 / 44100 as f64;
@@ -273,6 +331,10 @@ criterion_group!(
     benches,
     bench_fftfixedin,
     bench_fftfixedin_32,
+    bench_halfband_decimator,
+    bench_halfband_interpolator,
+    bench_async_block_cubic,
+    bench_async_block_nearest,
     bench_scalar_async_cubic_32,
     bench_scalar_async_linear_32,
     bench_scalar_async_nearest_32,
@@ -292,6 +354,10 @@ criterion_group!(
     benches,
     bench_fftfixedin,
     bench_fftfixedin_32,
+    bench_halfband_decimator,
+    bench_halfband_interpolator,
+    bench_async_block_cubic,
+    bench_async_block_nearest,
     bench_scalar_async_cubic_32,
     bench_scalar_async_linear_32,
     bench_scalar_async_nearest_32,
@@ -317,6 +383,10 @@ criterion_group!(
     benches,
     bench_fftfixedin,
     bench_fftfixedin_32,
+    bench_halfband_decimator,
+    bench_halfband_interpolator,
+    bench_async_block_cubic,
+    bench_async_block_nearest,
     bench_scalar_async_cubic_32,
     bench_scalar_async_linear_32,
     bench_scalar_async_nearest_32,
@@ -330,6 +400,10 @@ criterion_group!(
     benches,
     bench_fftfixedin,
     bench_fftfixedin_32,
+    bench_halfband_decimator,
+    bench_halfband_interpolator,
+    bench_async_block_cubic,
+    bench_async_block_nearest,
     bench_scalar_async_cubic_32,
     bench_scalar_async_linear_32,
     bench_scalar_async_nearest_32,
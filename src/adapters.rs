@@ -0,0 +1,552 @@
+use crate::{Sample, VecResampler};
+use num_traits::ToPrimitive;
+use std::io;
+use std::io::Read;
+
+/// The formats of raw PCM samples supported by [ResampleReader].
+/// All formats are little-endian, and integer samples are scaled so that full scale is 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleFormat {
+    /// 16 bit signed integer.
+    S16LE,
+    /// 24 bit signed integer, packed in 3 bytes.
+    S24LE,
+    /// 32 bit signed integer.
+    S32LE,
+    /// 32 bit float.
+    F32LE,
+    /// 64 bit float.
+    F64LE,
+}
+
+impl SampleFormat {
+    /// Get the number of bytes used for one sample.
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            SampleFormat::S16LE => 2,
+            SampleFormat::S24LE => 3,
+            SampleFormat::S32LE => 4,
+            SampleFormat::F32LE => 4,
+            SampleFormat::F64LE => 8,
+        }
+    }
+
+    /// Convert the bytes of one sample to a float value.
+    /// The slice must have the length given by "bytes_per_sample".
+    pub fn decode(&self, bytes: &[u8]) -> f64 {
+        match self {
+            SampleFormat::S16LE => i16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 32768.0,
+            SampleFormat::S24LE => {
+                (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f64 / 8388608.0
+            }
+            SampleFormat::S32LE => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / 2147483648.0
+            }
+            SampleFormat::F32LE => {
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+            }
+            SampleFormat::F64LE => {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(&bytes[..8]);
+                f64::from_le_bytes(buf)
+            }
+        }
+    }
+
+    /// Convert a float value to the bytes of one sample.
+    /// Values outside the range of the integer formats are clipped.
+    /// The slice must have the length given by "bytes_per_sample".
+    pub fn encode(&self, value: f64, bytes: &mut [u8]) {
+        match self {
+            SampleFormat::S16LE => {
+                let scaled = (value * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+                bytes[..2].copy_from_slice(&scaled.to_le_bytes());
+            }
+            SampleFormat::S24LE => {
+                let scaled = (value * 8388608.0).round().clamp(-8388608.0, 8388607.0) as i32;
+                bytes[..3].copy_from_slice(&scaled.to_le_bytes()[..3]);
+            }
+            SampleFormat::S32LE => {
+                let scaled = (value * 2147483648.0)
+                    .round()
+                    .clamp(-2147483648.0, 2147483647.0) as i32;
+                bytes[..4].copy_from_slice(&scaled.to_le_bytes());
+            }
+            SampleFormat::F32LE => bytes[..4].copy_from_slice(&(value as f32).to_le_bytes()),
+            SampleFormat::F64LE => bytes[..8].copy_from_slice(&value.to_le_bytes()),
+        }
+    }
+}
+
+/// Keeps track of the output of an adapter, for removing the delay of the resampler
+/// and for flushing it at the end of the input.
+struct OutputTrim {
+    resample_ratio: Option<f64>,
+    frames_in: usize,
+    frames_out: usize,
+    frames_to_skip: usize,
+}
+
+impl OutputTrim {
+    /// Create a new OutputTrim. With no resample ratio, all output is kept
+    /// and nothing is flushed.
+    fn new(resample_ratio: Option<f64>, delay: usize) -> Self {
+        OutputTrim {
+            resample_ratio,
+            frames_in: 0,
+            frames_out: 0,
+            frames_to_skip: if resample_ratio.is_some() { delay } else { 0 },
+        }
+    }
+
+    /// Get the number of output frames for the input so far,
+    /// or None if the output is not trimmed.
+    fn expected_frames_out(&self) -> Option<usize> {
+        self.resample_ratio
+            .map(|ratio| (self.frames_in as f64 * ratio).round() as usize)
+    }
+
+    /// Get the range of a chunk of `len` output frames that should be kept.
+    /// The delay is skipped, and nothing is kept past the expected number of output frames.
+    fn keep(&mut self, len: usize) -> std::ops::Range<usize> {
+        let skip = self.frames_to_skip.min(len);
+        self.frames_to_skip -= skip;
+        let mut end = len;
+        if let Some(expected) = self.expected_frames_out() {
+            end = end.min(skip + expected.saturating_sub(self.frames_out));
+        }
+        self.frames_out += end - skip;
+        skip..end
+    }
+
+    /// Check if the resampler must be flushed with more silence after the end of the input,
+    /// to produce the rest of the expected output.
+    fn needs_flush(&self) -> bool {
+        match self.expected_frames_out() {
+            Some(expected) => self.frames_out < expected,
+            None => false,
+        }
+    }
+}
+
+/// An iterator adapter that resamples a stream of frames.
+///
+/// Each item of the wrapped iterator is one frame, holding one sample per channel,
+/// and the adapter yields resampled frames in the same layout.
+/// The input is collected into chunks of the size needed by the resampler.
+/// When the wrapped iterator ends, the resampler is flushed with silence,
+/// so that the end of the input reaches the output.
+/// The delay of the resampler is removed, see [crate::Resampler::output_delay],
+/// and `n` input frames give `round(n * resample_ratio)` output frames.
+/// Use [ResampleIter::new_raw] to get the unmodified output of the resampler instead.
+///
+/// # Panics
+///
+/// The iterator panics if a frame does not have the number of channels
+/// given when creating the adapter.
+pub struct ResampleIter<T, I> {
+    frames: I,
+    resampler: Box<dyn VecResampler<T>>,
+    nbr_channels: usize,
+    input: Vec<Vec<T>>,
+    output: Vec<Vec<T>>,
+    position: usize,
+    end: usize,
+    trim: OutputTrim,
+    input_ended: bool,
+    done: bool,
+}
+
+impl<T, I> ResampleIter<T, I>
+where
+    T: Sample,
+{
+    /// Create a new ResampleIter
+    ///
+    /// Parameters are:
+    /// - `frames`: The iterator of input frames.
+    /// - `resampler`: The resampler to use, of any type.
+    /// - `resample_ratio`: The ratio between output and input sample rates of the resampler.
+    /// - `nbr_channels`: number of channels in input/output, must match the resampler.
+    pub fn new(
+        frames: I,
+        resampler: Box<dyn VecResampler<T>>,
+        resample_ratio: f64,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::new_with_trim(frames, resampler, Some(resample_ratio), nbr_channels)
+    }
+
+    /// Create a new ResampleIter that yields the raw output of the resampler.
+    /// The output starts with the delay of the resampler, and the last chunk of input
+    /// is only padded with silence up to the size needed by the resampler,
+    /// so the end of the input may not reach the output.
+    ///
+    /// Parameters are:
+    /// - `frames`: The iterator of input frames.
+    /// - `resampler`: The resampler to use, of any type.
+    /// - `nbr_channels`: number of channels in input/output, must match the resampler.
+    pub fn new_raw(frames: I, resampler: Box<dyn VecResampler<T>>, nbr_channels: usize) -> Self {
+        Self::new_with_trim(frames, resampler, None, nbr_channels)
+    }
+
+    fn new_with_trim(
+        frames: I,
+        resampler: Box<dyn VecResampler<T>>,
+        resample_ratio: Option<f64>,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new ResampleIter, ratio: {:?}, channels: {}",
+            resample_ratio, nbr_channels
+        );
+        let trim = OutputTrim::new(resample_ratio, resampler.output_delay());
+        ResampleIter {
+            frames,
+            resampler,
+            nbr_channels,
+            input: vec![Vec::new(); nbr_channels],
+            output: vec![Vec::new(); nbr_channels],
+            position: 0,
+            end: 0,
+            trim,
+            input_ended: false,
+            done: false,
+        }
+    }
+}
+
+impl<T, I> Iterator for ResampleIter<T, I>
+where
+    T: Sample,
+    I: Iterator,
+    I::Item: AsRef<[T]>,
+{
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Vec<T>> {
+        loop {
+            if self.position < self.end {
+                let frame = self.output.iter().map(|wave| wave[self.position]).collect();
+                self.position += 1;
+                return Some(frame);
+            }
+            if self.done {
+                return None;
+            }
+            let needed = self.resampler.nbr_frames_needed();
+            for wave in self.input.iter_mut() {
+                wave.clear();
+            }
+            if !self.input_ended {
+                for frame in self.frames.by_ref().take(needed) {
+                    let frame = frame.as_ref();
+                    assert_eq!(
+                        frame.len(),
+                        self.nbr_channels,
+                        "Wrong number of channels in input frame"
+                    );
+                    for (wave, value) in self.input.iter_mut().zip(frame.iter()) {
+                        wave.push(*value);
+                    }
+                }
+            }
+            let frames = self.input.first().map(|wave| wave.len()).unwrap_or(0);
+            self.trim.frames_in += frames;
+            if frames < needed {
+                self.input_ended = true;
+                if frames == 0 && !self.trim.needs_flush() {
+                    self.done = true;
+                    return None;
+                }
+                for wave in self.input.iter_mut() {
+                    wave.resize(needed, T::zero());
+                }
+            }
+            self.output = self
+                .resampler
+                .process(&self.input)
+                .expect("Resampling failed");
+            let range = self
+                .trim
+                .keep(self.output.first().map(|wave| wave.len()).unwrap_or(0));
+            self.position = range.start;
+            self.end = range.end;
+            self.done = self.input_ended && !self.trim.needs_flush();
+        }
+    }
+}
+
+/// A [std::io::Read] adapter that resamples raw interleaved PCM audio on the fly.
+///
+/// The bytes read from the wrapped reader are converted from the given sample format,
+/// resampled in chunks of the size needed by the resampler, and converted back to the same format.
+/// When the wrapped reader ends, the resampler is flushed with silence,
+/// so that the end of the input reaches the output.
+/// The delay of the resampler is removed, see [crate::Resampler::output_delay],
+/// and `n` input frames give `round(n * resample_ratio)` output frames.
+/// Use [ResampleReader::new_raw] to get the unmodified output of the resampler instead.
+pub struct ResampleReader<T, R> {
+    reader: R,
+    resampler: Box<dyn VecResampler<T>>,
+    format: SampleFormat,
+    nbr_channels: usize,
+    bytes_in: Vec<u8>,
+    input: Vec<Vec<T>>,
+    bytes_out: Vec<u8>,
+    position: usize,
+    trim: OutputTrim,
+    input_ended: bool,
+    done: bool,
+}
+
+impl<T, R> ResampleReader<T, R>
+where
+    T: Sample + ToPrimitive,
+    R: Read,
+{
+    /// Create a new ResampleReader
+    ///
+    /// Parameters are:
+    /// - `reader`: The reader of raw input audio.
+    /// - `resampler`: The resampler to use, of any type.
+    /// - `resample_ratio`: The ratio between output and input sample rates of the resampler.
+    /// - `format`: The sample format of both input and output.
+    /// - `nbr_channels`: number of channels in input/output, must match the resampler.
+    pub fn new(
+        reader: R,
+        resampler: Box<dyn VecResampler<T>>,
+        resample_ratio: f64,
+        format: SampleFormat,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::new_with_trim(
+            reader,
+            resampler,
+            Some(resample_ratio),
+            format,
+            nbr_channels,
+        )
+    }
+
+    /// Create a new ResampleReader that gives the raw output of the resampler.
+    /// The output starts with the delay of the resampler, and the last chunk of input
+    /// is only padded with silence up to the size needed by the resampler,
+    /// so the end of the input may not reach the output.
+    ///
+    /// Parameters are:
+    /// - `reader`: The reader of raw input audio.
+    /// - `resampler`: The resampler to use, of any type.
+    /// - `format`: The sample format of both input and output.
+    /// - `nbr_channels`: number of channels in input/output, must match the resampler.
+    pub fn new_raw(
+        reader: R,
+        resampler: Box<dyn VecResampler<T>>,
+        format: SampleFormat,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::new_with_trim(reader, resampler, None, format, nbr_channels)
+    }
+
+    fn new_with_trim(
+        reader: R,
+        resampler: Box<dyn VecResampler<T>>,
+        resample_ratio: Option<f64>,
+        format: SampleFormat,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new ResampleReader, ratio: {:?}, format: {:?}, channels: {}",
+            resample_ratio, format, nbr_channels
+        );
+        let trim = OutputTrim::new(resample_ratio, resampler.output_delay());
+        ResampleReader {
+            reader,
+            resampler,
+            format,
+            nbr_channels,
+            bytes_in: Vec::new(),
+            input: vec![Vec::new(); nbr_channels],
+            bytes_out: Vec::new(),
+            position: 0,
+            trim,
+            input_ended: false,
+            done: false,
+        }
+    }
+
+    /// Get back the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read and resample the next chunk of input.
+    fn process_chunk(&mut self) -> io::Result<()> {
+        let needed = self.resampler.nbr_frames_needed();
+        let bytes_per_sample = self.format.bytes_per_sample();
+        let frame_bytes = bytes_per_sample * self.nbr_channels;
+        self.bytes_in.resize(needed * frame_bytes, 0);
+        let mut filled = 0;
+        while !self.input_ended && filled < self.bytes_in.len() {
+            match self.reader.read(&mut self.bytes_in[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        self.bytes_out.clear();
+        self.position = 0;
+        self.trim.frames_in += filled / frame_bytes;
+        if filled < self.bytes_in.len() {
+            self.input_ended = true;
+            if filled < frame_bytes && !self.trim.needs_flush() {
+                self.done = true;
+                return Ok(());
+            }
+            // pad with silence, also any incomplete frame at the end
+            let complete = filled - filled % frame_bytes;
+            self.bytes_in[complete..].iter_mut().for_each(|b| *b = 0);
+        }
+        for wave in self.input.iter_mut() {
+            wave.clear();
+        }
+        for frame in self.bytes_in.chunks(frame_bytes) {
+            for (wave, bytes) in self.input.iter_mut().zip(frame.chunks(bytes_per_sample)) {
+                wave.push(T::coerce(self.format.decode(bytes)));
+            }
+        }
+        let output = self
+            .resampler
+            .process(&self.input)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let range = self
+            .trim
+            .keep(output.first().map(|wave| wave.len()).unwrap_or(0));
+        self.bytes_out.resize(range.len() * frame_bytes, 0);
+        for (n, frame) in self.bytes_out.chunks_mut(frame_bytes).enumerate() {
+            for (wave, bytes) in output.iter().zip(frame.chunks_mut(bytes_per_sample)) {
+                self.format
+                    .encode(wave[range.start + n].to_f64().unwrap_or(0.0), bytes);
+            }
+        }
+        self.done = self.input_ended && !self.trim.needs_flush();
+        Ok(())
+    }
+}
+
+impl<T, R> Read for ResampleReader<T, R>
+where
+    T: Sample + ToPrimitive,
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.bytes_out.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.process_chunk()?;
+        }
+        let count = buf.len().min(self.bytes_out.len() - self.position);
+        buf[..count].copy_from_slice(&self.bytes_out[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        FftFixedInOut, InterpolationParameters, InterpolationType, ResampleIter, ResampleReader,
+        Resampler, SampleFormat, SincFixedIn, WindowFunction,
+    };
+    use std::io::Read;
+
+    #[test]
+    fn sample_formats() {
+        let mut bytes = [0u8; 8];
+        for format in [
+            SampleFormat::S16LE,
+            SampleFormat::S24LE,
+            SampleFormat::S32LE,
+            SampleFormat::F32LE,
+            SampleFormat::F64LE,
+        ]
+        .iter()
+        {
+            let len = format.bytes_per_sample();
+            for value in [0.0, 0.5, -0.25, -1.0].iter() {
+                format.encode(*value, &mut bytes[..len]);
+                assert_eq!(format.decode(&bytes[..len]), *value);
+            }
+        }
+        SampleFormat::S16LE.encode(1.5, &mut bytes[..2]);
+        assert_eq!(SampleFormat::S16LE.decode(&bytes[..2]), 32767.0 / 32768.0);
+        SampleFormat::S24LE.encode(-1.5, &mut bytes[..3]);
+        assert_eq!(SampleFormat::S24LE.decode(&bytes[..3]), -1.0);
+    }
+
+    #[test]
+    fn iterate_frames() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let resampler = SincFixedIn::<f64>::new(2.0, params, 256, 2);
+        let frames = (0..1000).map(|n| {
+            let value = (2.0 * std::f64::consts::PI * n as f64 / 100.0).sin();
+            [value, -value]
+        });
+        let output: Vec<Vec<f64>> =
+            ResampleIter::new(frames, Box::new(resampler), 2.0, 2).collect();
+        assert_eq!(output.len(), 2000);
+        assert!(output.iter().all(|frame| frame.len() == 2));
+        // the delay is removed, skip the abrupt start and end
+        for (n, frame) in output.iter().enumerate().take(1900).skip(100) {
+            let expected = (2.0 * std::f64::consts::PI * n as f64 / 200.0).sin();
+            assert!((frame[0] - expected).abs() < 1.0e-3, "frame {}", n);
+            assert!((frame[1] + expected).abs() < 1.0e-3, "frame {}", n);
+        }
+    }
+
+    #[test]
+    fn iterate_frames_raw() {
+        let resampler = FftFixedInOut::<f64>::new(44100, 48000, 441, 1);
+        let delay = resampler.output_delay();
+        let frames = (0..1000).map(|_| [0.5]);
+        let output: Vec<Vec<f64>> = ResampleIter::new_raw(frames, Box::new(resampler), 1).collect();
+        // three chunks of 480 frames, starting with the delay
+        assert_eq!(output.len(), 3 * 480);
+        assert!(output[delay / 2][0].abs() < 1.0e-3);
+        assert!((output[delay + 500][0] - 0.5).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn read_bytes() {
+        let resampler = FftFixedInOut::<f32>::new(44100, 48000, 441, 1);
+        let mut bytes = Vec::new();
+        for n in 0..4410 {
+            // the last 441 frames are louder
+            let value: i16 = if n < 3969 { 4096 } else { 8192 };
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut reader = ResampleReader::new(
+            bytes.as_slice(),
+            Box::new(resampler),
+            48000.0 / 44100.0,
+            SampleFormat::S16LE,
+            1,
+        );
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output.len(), 2 * 4800);
+        let sample = |n: usize| i16::from_le_bytes([output[2 * n], output[2 * n + 1]]);
+        // the delay is removed, and the last input frames reach the output
+        assert!((sample(100) - 4096).abs() < 10, "got {}", sample(100));
+        assert!((sample(4000) - 4096).abs() < 10, "got {}", sample(4000));
+        assert!((sample(4700) - 8192).abs() < 10, "got {}", sample(4700));
+    }
+}
@@ -0,0 +1,321 @@
+//! A command line tool for resampling raw interleaved audio files.
+//!
+//! Build and run it with the `cli` feature:
+//! ```text
+//! cargo run --release --features cli --bin rubato -- [options] <input> <output> <fs_in> <fs_out>
+//! ```
+//! Run it with `--help` for a list of the options.
+
+extern crate env_logger;
+extern crate rubato;
+
+use rubato::{
+    FftFixedIn, FftFixedInOut, FftFixedOut, Quality, ResampleReader, SampleFormat, SincFixedIn,
+    SincFixedOut, VecResampler,
+};
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: rubato [options] <input> <output> <fs_in> <fs_out>
+
+Resample a file of raw interleaved samples.
+
+Options:
+  -r, --resampler <type>  sincfixedin, sincfixedout, fftfixedin, fftfixedout
+                          or fftfixedinout [default: sincfixedin]
+  -q, --quality <preset>  fast, balanced or best [default: balanced]
+  -f, --format <format>   f32, f64, i16, i24 or i32, little-endian [default: f64]
+  -c, --channels <n>      number of channels [default: 2]
+  -n, --chunk <n>         chunk size in frames [default: 1024]
+  -h, --help              print this help";
+
+/// The types of resampler that can be selected.
+#[derive(Debug, Clone, Copy)]
+enum ResamplerType {
+    SincFixedIn,
+    SincFixedOut,
+    FftFixedIn,
+    FftFixedOut,
+    FftFixedInOut,
+}
+
+/// The command line options.
+struct Options {
+    input: String,
+    output: String,
+    fs_in: usize,
+    fs_out: usize,
+    resampler: ResamplerType,
+    quality: Quality,
+    format: SampleFormat,
+    channels: usize,
+    chunk_size: usize,
+}
+
+/// Parse a number given to an option.
+fn parse_number(option: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse::<usize>()
+        .ok()
+        .filter(|v| *v > 0)
+        .ok_or_else(|| format!("Invalid value for {}: {}", option, value))
+}
+
+/// Parse the command line arguments.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut positional = Vec::new();
+    let mut resampler = ResamplerType::SincFixedIn;
+    let mut quality = Quality::Balanced;
+    let mut format = SampleFormat::F64LE;
+    let mut channels = 2;
+    let mut chunk_size = 1024;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            positional.push(arg.clone());
+            continue;
+        }
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "-r" | "--resampler" => {
+                resampler = match value.as_str() {
+                    "sincfixedin" => ResamplerType::SincFixedIn,
+                    "sincfixedout" => ResamplerType::SincFixedOut,
+                    "fftfixedin" => ResamplerType::FftFixedIn,
+                    "fftfixedout" => ResamplerType::FftFixedOut,
+                    "fftfixedinout" => ResamplerType::FftFixedInOut,
+                    _ => return Err(format!("Unknown resampler: {}", value)),
+                }
+            }
+            "-q" | "--quality" => {
+                quality = parse_quality(value)
+                    .ok_or_else(|| format!("Unknown quality preset: {}", value))?;
+            }
+            "-f" | "--format" => {
+                format = match value.as_str() {
+                    "f32" => SampleFormat::F32LE,
+                    "f64" => SampleFormat::F64LE,
+                    "i16" => SampleFormat::S16LE,
+                    "i24" => SampleFormat::S24LE,
+                    "i32" => SampleFormat::S32LE,
+                    _ => return Err(format!("Unknown sample format: {}", value)),
+                }
+            }
+            "-c" | "--channels" => channels = parse_number(arg, value)?,
+            "-n" | "--chunk" => chunk_size = parse_number(arg, value)?,
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    if positional.len() != 4 {
+        return Err("Expected an input file, an output file and two sample rates".to_string());
+    }
+    Ok(Options {
+        input: positional[0].clone(),
+        output: positional[1].clone(),
+        fs_in: parse_number("fs_in", &positional[2])?,
+        fs_out: parse_number("fs_out", &positional[3])?,
+        resampler,
+        quality,
+        format,
+        channels,
+        chunk_size,
+    })
+}
+
+/// Parse the name of a quality preset.
+fn parse_quality(name: &str) -> Option<Quality> {
+    match name {
+        "fast" => Some(Quality::Fast),
+        "balanced" => Some(Quality::Balanced),
+        "best" => Some(Quality::Best),
+        _ => None,
+    }
+}
+
+fn make_resampler(options: &Options) -> Box<dyn VecResampler<f64>> {
+    let ratio = options.fs_out as f64 / options.fs_in as f64;
+    let parameters = options.quality.interpolation_parameters();
+    let sub_chunks = options.quality.sub_chunks();
+    match options.resampler {
+        ResamplerType::SincFixedIn => Box::new(SincFixedIn::<f64>::new(
+            ratio,
+            parameters,
+            options.chunk_size,
+            options.channels,
+        )),
+        ResamplerType::SincFixedOut => Box::new(SincFixedOut::<f64>::new(
+            ratio,
+            parameters,
+            options.chunk_size,
+            options.channels,
+        )),
+        ResamplerType::FftFixedIn => Box::new(FftFixedIn::<f64>::new(
+            options.fs_in,
+            options.fs_out,
+            options.chunk_size,
+            sub_chunks,
+            options.channels,
+        )),
+        ResamplerType::FftFixedOut => Box::new(FftFixedOut::<f64>::new(
+            options.fs_in,
+            options.fs_out,
+            options.chunk_size,
+            sub_chunks,
+            options.channels,
+        )),
+        ResamplerType::FftFixedInOut => Box::new(FftFixedInOut::<f64>::new(
+            options.fs_in,
+            options.fs_out,
+            options.chunk_size,
+            options.channels,
+        )),
+    }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let file_in =
+        File::open(&options.input).map_err(|e| format!("Can't open {}: {}", options.input, e))?;
+    let file_out = File::create(&options.output)
+        .map_err(|e| format!("Can't create {}: {}", options.output, e))?;
+
+    let resampler = make_resampler(&options);
+    let delay = resampler.output_delay();
+    println!(
+        "Resampling {} to {}, {} Hz to {} Hz, {} channels, {:?}, {:?} quality",
+        options.input,
+        options.output,
+        options.fs_in,
+        options.fs_out,
+        options.channels,
+        options.resampler,
+        options.quality
+    );
+    println!(
+        "Removing latency of {} frames ({:.2} ms)",
+        delay,
+        1000.0 * delay as f64 / options.fs_out as f64
+    );
+
+    // The reader removes the latency, and flushes the resampler at the end of the input.
+    let mut reader = ResampleReader::new(
+        BufReader::new(file_in),
+        resampler,
+        options.fs_out as f64 / options.fs_in as f64,
+        options.format,
+        options.channels,
+    );
+    let mut writer = BufWriter::new(file_out);
+    let start = Instant::now();
+    let bytes = std::io::copy(&mut reader, &mut writer).map_err(|e| e.to_string())?;
+    let duration = start.elapsed();
+
+    let frames_out = bytes as usize / (options.format.bytes_per_sample() * options.channels);
+    let audio_seconds = frames_out as f64 / options.fs_out as f64;
+    println!(
+        "Wrote {} frames ({:.2} s of audio) in {:.3} s, {:.1}x real time",
+        frames_out,
+        audio_seconds,
+        duration.as_secs_f64(),
+        audio_seconds / duration.as_secs_f64()
+    );
+    Ok(())
+}
+
+fn main() {
+    env_logger::init();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = parse_args(&args).and_then(run);
+    if let Err(err) = result {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_args, ResamplerType};
+    use rubato::{Quality, SampleFormat};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let options = parse_args(&args(&["in.raw", "out.raw", "44100", "48000"])).unwrap();
+        assert_eq!(options.input, "in.raw");
+        assert_eq!(options.output, "out.raw");
+        assert_eq!(options.fs_in, 44100);
+        assert_eq!(options.fs_out, 48000);
+        assert!(matches!(options.resampler, ResamplerType::SincFixedIn));
+        assert_eq!(options.quality, Quality::Balanced);
+        assert_eq!(options.format, SampleFormat::F64LE);
+        assert_eq!(options.channels, 2);
+        assert_eq!(options.chunk_size, 1024);
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = parse_args(&args(&[
+            "-r",
+            "fftfixedinout",
+            "in.raw",
+            "--quality",
+            "best",
+            "-f",
+            "i24",
+            "out.raw",
+            "--channels",
+            "6",
+            "-n",
+            "512",
+            "96000",
+            "44100",
+        ]))
+        .unwrap();
+        assert_eq!(options.input, "in.raw");
+        assert_eq!(options.output, "out.raw");
+        assert_eq!(options.fs_in, 96000);
+        assert_eq!(options.fs_out, 44100);
+        assert!(matches!(options.resampler, ResamplerType::FftFixedInOut));
+        assert_eq!(options.quality, Quality::Best);
+        assert_eq!(options.format, SampleFormat::S24LE);
+        assert_eq!(options.channels, 6);
+        assert_eq!(options.chunk_size, 512);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let positional = ["in.raw", "out.raw", "44100", "48000"];
+        assert!(parse_args(&args(&positional[..3])).is_err());
+        assert!(parse_args(&args(&["in.raw", "out.raw", "44100", "0"])).is_err());
+        assert!(parse_args(&args(&["in.raw", "out.raw", "44100", "fast"])).is_err());
+        for (option, value) in [
+            ("-r", "sinc"),
+            ("-q", "perfect"),
+            ("-f", "u8"),
+            ("-c", "0"),
+            ("-n", "-1"),
+            ("-x", "1"),
+        ]
+        .iter()
+        {
+            let mut arguments = args(&positional);
+            arguments.push(option.to_string());
+            arguments.push(value.to_string());
+            assert!(parse_args(&arguments).is_err(), "{} {}", option, value);
+        }
+        let mut arguments = args(&positional);
+        arguments.push("-c".to_string());
+        assert!(parse_args(&arguments).is_err());
+    }
+}
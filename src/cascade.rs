@@ -0,0 +1,396 @@
+use crate::asynchro::{sinc_output_delay, SincFixedIn};
+use crate::error::{ResampleError, ResampleResult};
+use crate::halfband::{HalfbandDecimator, HalfbandInterpolator};
+use crate::{InterpolationParameters, Resampler, Sample};
+
+/// Number of nonzero coefficients on each side of the center tap of the halfband filters.
+/// With the BlackmanHarris2 window, this gives about 180 dB of attenuation
+/// in the stop band of all stages of the cascade.
+const HALFBAND_COEFFS: usize = 24;
+
+/// An asynchronous resampler for large ratios, that accepts a fixed number of audio frames
+/// for input and returns a variable number of frames.
+///
+/// The resampling is done in several stages. Each factor of two of the ratio is handled by a
+/// halfband filter, and what remains is handled by a [SincFixedIn] with a ratio between 0.25 and 4.
+/// When downsampling the halfband decimators come first, and when upsampling the halfband
+/// interpolators come last, so that the sinc stage always runs at the lowest sample rate.
+/// This keeps the cost per output frame low, and lets the sinc filter keep a reasonable length
+/// compared to the width of the transition band.
+pub struct CascadeFixedIn<T> {
+    nbr_channels: usize,
+    chunk_size: usize,
+    resample_ratio: f64,
+    resample_ratio_original: f64,
+    halfband_ratio: f64,
+    oversampling_factor: usize,
+    decimators: Vec<HalfbandDecimator<T>>,
+    interpolators: Vec<HalfbandInterpolator<T>>,
+    resampler: SincFixedIn<T>,
+}
+
+impl<T> CascadeFixedIn<T>
+where
+    T: Sample,
+{
+    /// Create a new CascadeFixedIn
+    ///
+    /// Parameters are:
+    /// - `resample_ratio`: Ratio between output and input sample rates.
+    /// - `parameters`: Parameters for interpolation in the sinc stage, see `InterpolationParameters`.
+    ///   The window function is also used for the halfband stages.
+    /// - `chunk_size`: desired size of input data in frames, actual value may be different
+    ///   since it must be divisible by the decimation factor of the halfband stages.
+    /// - `nbr_channels`: number of channels in input/output
+    pub fn new(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new CascadeFixedIn, ratio: {}, chunk_size: {}, channels: {}, parameters: {:?}",
+            resample_ratio, chunk_size, nbr_channels, parameters
+        );
+        let mut nbr_stages = 0;
+        let halfband_ratio = if resample_ratio < 1.0 {
+            while resample_ratio * 2.0f64.powi(nbr_stages + 1) <= 0.5 {
+                nbr_stages += 1;
+            }
+            0.5f64.powi(nbr_stages)
+        } else {
+            while resample_ratio / 2.0f64.powi(nbr_stages + 1) >= 2.0 {
+                nbr_stages += 1;
+            }
+            2.0f64.powi(nbr_stages)
+        };
+        let mut chunk_size = chunk_size;
+        let mut sinc_chunk_size = chunk_size;
+        let mut decimators = Vec::new();
+        let mut interpolators = Vec::new();
+        if resample_ratio < 1.0 {
+            let factor = 1 << nbr_stages;
+            sinc_chunk_size = (chunk_size + factor - 1) / factor;
+            chunk_size = factor * sinc_chunk_size;
+            for stage in 0..nbr_stages {
+                decimators.push(HalfbandDecimator::new(
+                    HALFBAND_COEFFS,
+                    parameters.window,
+                    chunk_size >> stage,
+                    nbr_channels,
+                ));
+            }
+        } else {
+            // the sinc stage output varies in length, use the nominal length
+            let sinc_output_size = (chunk_size as f64 * resample_ratio / halfband_ratio).ceil();
+            for stage in 0..nbr_stages {
+                interpolators.push(HalfbandInterpolator::new(
+                    HALFBAND_COEFFS,
+                    parameters.window,
+                    (sinc_output_size as usize) << stage,
+                    nbr_channels,
+                ));
+            }
+        }
+        debug!(
+            "Using {} halfband stages, sinc stage ratio: {}, chunk_size: {}",
+            nbr_stages,
+            resample_ratio / halfband_ratio,
+            chunk_size
+        );
+        let oversampling_factor = parameters.oversampling_factor;
+        let resampler = SincFixedIn::new(
+            resample_ratio / halfband_ratio,
+            parameters,
+            sinc_chunk_size,
+            nbr_channels,
+        );
+
+        CascadeFixedIn {
+            nbr_channels,
+            chunk_size,
+            resample_ratio,
+            resample_ratio_original: resample_ratio,
+            halfband_ratio,
+            oversampling_factor,
+            decimators,
+            interpolators,
+            resampler,
+        }
+    }
+}
+
+impl<T> Resampler<T> for CascadeFixedIn<T>
+where
+    T: Sample,
+{
+    /// Resample a chunk of audio. The input length is fixed, and the output varies in length.
+    /// If the waveform for a channel is empty, this channel will be ignored and produce a
+    /// corresponding empty output waveform.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input data is not equal
+    /// to the number of channels and chunk size of the instance.
+    fn process<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<Vec<Vec<T>>> {
+        if wave_in.len() != self.nbr_channels {
+            return Err(ResampleError::WrongNumberOfChannels {
+                expected: self.nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        for (chan, wave) in wave_in.iter().enumerate() {
+            let wave = wave.as_ref();
+            if !wave.is_empty() && wave.len() != self.chunk_size {
+                return Err(ResampleError::WrongNumberOfFrames {
+                    channel: chan,
+                    expected: self.chunk_size,
+                    actual: wave.len(),
+                });
+            }
+        }
+
+        let mut wave_out = if self.decimators.is_empty() {
+            self.resampler.process(wave_in)?
+        } else {
+            let mut waves: Vec<Vec<T>> = wave_in.iter().map(|w| w.as_ref().to_vec()).collect();
+            for decimator in self.decimators.iter_mut() {
+                waves = waves
+                    .iter()
+                    .enumerate()
+                    .map(|(chan, wave)| {
+                        let mut decimated = vec![T::zero(); wave.len() / 2];
+                        decimator.process_channel(chan, wave, &mut decimated);
+                        decimated
+                    })
+                    .collect();
+            }
+            self.resampler.process(&waves)?
+        };
+        for interpolator in self.interpolators.iter_mut() {
+            wave_out = wave_out
+                .iter()
+                .enumerate()
+                .map(|(chan, wave)| {
+                    let mut interpolated = vec![T::zero(); 2 * wave.len()];
+                    interpolator.process_channel(chan, wave, &mut interpolated);
+                    interpolated
+                })
+                .collect();
+        }
+        Ok(wave_out)
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    /// Will always return the chunk_size of the instance.
+    fn nbr_frames_needed(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Get the largest number of frames that one call to "process" can return.
+    fn output_frames_max(&self) -> usize {
+        self.resampler.output_frames_max() << self.interpolators.len()
+    }
+
+    /// Update the resample ratio. New value must be within +-10% of the original one
+    fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()> {
+        self.resampler
+            .set_resample_ratio(new_ratio / self.halfband_ratio)?;
+        self.resample_ratio = new_ratio;
+        Ok(())
+    }
+
+    /// Update the resample ratio relative to the original one
+    fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+        let new_ratio = self.resample_ratio_original * rel_ratio;
+        self.set_resample_ratio(new_ratio)
+    }
+
+    /// Get the delay of the output relative to the input, in output frames.
+    /// This is the sum of the delays of all stages.
+    fn output_delay(&self) -> usize {
+        let sinc_ratio = self.resample_ratio / self.halfband_ratio;
+        // delay in frames at the input rate of the sinc stage
+        let mut delay = 0.0;
+        for decimator in self.decimators.iter() {
+            delay = delay / 2.0 + decimator.output_delay() as f64;
+        }
+        delay = delay * sinc_ratio + sinc_output_delay(sinc_ratio, self.oversampling_factor);
+        for interpolator in self.interpolators.iter() {
+            delay = 2.0 * delay + interpolator.output_delay() as f64;
+        }
+        delay.round().max(0.0) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CascadeFixedIn;
+    use crate::Resampler;
+    use crate::{InterpolationParameters, InterpolationType, WindowFunction};
+
+    fn basic_params() -> InterpolationParameters {
+        InterpolationParameters {
+            sinc_len: 128,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+
+    // Resample a sine and return the delay in output frames found by fitting
+    // the output to a sine with the same frequency.
+    fn measure_delay(fs_in: f64, fs_out: f64, freq: f64, chunk_size: usize) -> (f64, usize, f64) {
+        let mut resampler =
+            CascadeFixedIn::<f64>::new(fs_out / fs_in, basic_params(), chunk_size, 1);
+        let chunk_size = resampler.nbr_frames_needed();
+        let mut output = Vec::new();
+        for chunk in 0..40 {
+            let wave: Vec<f64> = (0..chunk_size)
+                .map(|n| {
+                    let t = (chunk * chunk_size + n) as f64 / fs_in;
+                    (2.0 * std::f64::consts::PI * freq * t).sin()
+                })
+                .collect();
+            let out = resampler.process(&[wave]).unwrap();
+            output.extend_from_slice(&out[0]);
+        }
+        let omega = 2.0 * std::f64::consts::PI * freq / fs_out;
+        let skip = output.len() / 2;
+        let (mut ss, mut sc, mut cc, mut ys, mut yc) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (n, y) in output.iter().enumerate().skip(skip) {
+            let (s, c) = (omega * n as f64).sin_cos();
+            ss += s * s;
+            sc += s * c;
+            cc += c * c;
+            ys += y * s;
+            yc += y * c;
+        }
+        let det = ss * cc - sc * sc;
+        let a = (ys * cc - yc * sc) / det;
+        let b = (yc * ss - ys * sc) / det;
+        let mut residual: f64 = 0.0;
+        for (n, y) in output.iter().enumerate().skip(skip) {
+            let (s, c) = (omega * n as f64).sin_cos();
+            residual = residual.max((y - a * s - b * c).abs());
+        }
+        // y = sin(omega * (n - delay)) = cos(omega * delay) * s - sin(omega * delay) * c
+        let delay = (-b).atan2(a) / omega;
+        (delay, resampler.output_delay(), residual)
+    }
+
+    #[test]
+    fn make_resampler_down() {
+        let mut resampler = CascadeFixedIn::<f64>::new(8000.0 / 384000.0, basic_params(), 1000, 2);
+        assert_eq!(resampler.nbr_frames_needed(), 1008);
+        let waves = vec![vec![0.0f64; 1008]; 2];
+        let mut total = 0;
+        for _ in 0..10 {
+            let out = resampler.process(&waves).unwrap();
+            assert_eq!(out.len(), 2);
+            assert_eq!(out[0].len(), out[1].len());
+            total += out[0].len();
+        }
+        // the first chunk is shorter because of the startup of the sinc stage
+        assert!(total > 180 && total <= 210, "got {} frames", total);
+    }
+
+    #[test]
+    fn make_resampler_up() {
+        let mut resampler = CascadeFixedIn::<f64>::new(384000.0 / 8000.0, basic_params(), 1024, 2);
+        assert_eq!(resampler.nbr_frames_needed(), 1024);
+        let waves = vec![vec![0.0f64; 1024]; 2];
+        let mut total = 0;
+        for _ in 0..10 {
+            let out = resampler.process(&waves).unwrap();
+            assert_eq!(out[0].len(), out[1].len());
+            assert_eq!(out[0].len() % 16, 0);
+            total += out[0].len();
+        }
+        assert!(
+            total > 48 * 10000 && total <= 48 * 10240,
+            "got {} frames",
+            total
+        );
+    }
+
+    #[test]
+    fn make_resampler_skipped() {
+        let mut resampler = CascadeFixedIn::<f32>::new(0.1, basic_params(), 1024, 2);
+        let waves = vec![vec![0.0f32; 1024], Vec::new()];
+        let out = resampler.process(&waves).unwrap();
+        assert!(!out[0].is_empty());
+        assert!(out[1].is_empty());
+    }
+
+    #[test]
+    fn wrong_number_of_frames() {
+        let mut resampler = CascadeFixedIn::<f64>::new(0.02, basic_params(), 1000, 1);
+        assert_eq!(resampler.nbr_frames_needed(), 1008);
+        let waves = vec![vec![0.0f64; 1000]];
+        assert!(resampler.process(&waves).is_err());
+    }
+
+    #[test]
+    fn update_ratio() {
+        let mut resampler = CascadeFixedIn::<f64>::new(0.1, basic_params(), 1024, 1);
+        assert!(resampler.set_resample_ratio(0.105).is_ok());
+        assert!(resampler.set_resample_ratio_relative(0.95).is_ok());
+        assert!(resampler.set_resample_ratio(0.2).is_err());
+    }
+
+    #[test]
+    fn delay_down() {
+        let (measured, reported, residual) = measure_delay(384000.0, 8000.0, 100.0, 1024);
+        assert!(
+            (measured - reported as f64).abs() < 0.6,
+            "{} {}",
+            measured,
+            reported
+        );
+        assert!(residual < 1.0e-4, "residual {}", residual);
+    }
+
+    #[test]
+    fn delay_up() {
+        let (measured, reported, residual) = measure_delay(8000.0, 384000.0, 100.0, 256);
+        assert!(
+            (measured - reported as f64).abs() < 0.6,
+            "{} {}",
+            measured,
+            reported
+        );
+        assert!(residual < 1.0e-4, "residual {}", residual);
+    }
+
+    #[test]
+    fn delay_no_halfband() {
+        let (measured, reported, _residual) = measure_delay(44100.0, 48000.0, 100.0, 1024);
+        assert!(
+            (measured - reported as f64).abs() < 0.6,
+            "{} {}",
+            measured,
+            reported
+        );
+    }
+
+    #[test]
+    fn suppress_aliasing() {
+        // 6 kHz is above the output Nyquist frequency and must be removed.
+        let mut resampler = CascadeFixedIn::<f64>::new(8000.0 / 384000.0, basic_params(), 1024, 1);
+        let mut maxval: f64 = 0.0;
+        for chunk in 0..20 {
+            let wave: Vec<f64> = (0..1024)
+                .map(|n| {
+                    let t = (chunk * 1024 + n) as f64 / 384000.0;
+                    (2.0 * std::f64::consts::PI * 6000.0 * t).sin()
+                })
+                .collect();
+            let out = resampler.process(&[wave]).unwrap();
+            if chunk > 2 {
+                maxval = out[0].iter().fold(maxval, |acc, y| acc.max(y.abs()));
+            }
+        }
+        assert!(maxval < 1.0e-5, "max value {}", maxval);
+    }
+}
@@ -0,0 +1,523 @@
+use crate::error::{ResampleError, ResampleResult};
+use crate::{FftFixedOut, InterpolationParameters, Sample, SincFixedOut, VecResampler};
+use num_integer as integer;
+
+/// The type of resampler used for the segments of a [Concatenator].
+#[derive(Debug, Clone)]
+pub enum SegmentResampler {
+    /// Asynchronous resampling with [SincFixedOut], using the given parameters.
+    Sinc(InterpolationParameters),
+    /// Synchronous resampling with [FftFixedOut], using the given number of sub chunks.
+    Fft(usize),
+}
+
+/// Create the resampler for a segment with the given input sample rate, and get the number
+/// of frames its output starts before the start of the segment, and keeps going after the end.
+fn make_segment_resampler<T>(
+    resampler_type: &SegmentResampler,
+    fs_in: usize,
+    fs_out: usize,
+    chunk_size: usize,
+    nbr_channels: usize,
+) -> (Box<dyn VecResampler<T>>, usize, usize)
+where
+    T: Sample,
+{
+    match resampler_type {
+        SegmentResampler::Sinc(parameters) => {
+            let mut resampler = SincFixedOut::<T>::new(
+                fs_out as f64 / fs_in as f64,
+                parameters.clone(),
+                chunk_size,
+                nbr_channels,
+            );
+            resampler.align_output_delay();
+            let lead_len = resampler.output_delay() + resampler.include_pre_response();
+            let tail_len = resampler.tail_len();
+            (Box::new(resampler), lead_len, tail_len)
+        }
+        SegmentResampler::Fft(sub_chunks) => {
+            // Use an even number of the smallest possible fft chunks,
+            // to make the delay a whole number of frames.
+            let min_chunk_out = fs_out / integer::gcd(fs_in, fs_out);
+            let mut fft_chunks = (chunk_size / sub_chunks + min_chunk_out - 1) / min_chunk_out;
+            fft_chunks += fft_chunks % 2;
+            let resampler = FftFixedOut::<T>::new(
+                fs_in,
+                fs_out,
+                fft_chunks * min_chunk_out * sub_chunks,
+                *sub_chunks,
+                nbr_channels,
+            );
+            // the filter is centered on the delay
+            let lead_len = resampler.output_delay();
+            let tail_len = resampler.tail_len();
+            (Box::new(resampler), lead_len, tail_len)
+        }
+    }
+}
+
+/// A helper for joining segments with different sample rates into one continuous output stream.
+///
+/// Each segment is resampled by its own resampler, that is created when the segment starts.
+/// When a segment ends, the resampler is flushed so that no input is lost, and the output is trimmed
+/// so that a segment of `n` frames at `fs_in` gives exactly `round(n * fs_out / fs_in)` frames.
+/// The delay of the resampler is removed, so the segments join without gaps or overlaps.
+///
+/// The response of the resampler filter to the start and end of a segment reaches past the
+/// segment on both sides. This part is added to the end of the previous segment
+/// and the start of the next one. Since resampling is linear, a signal that continues across
+/// a join at the same sample rate gives the same output as without the join.
+/// For this, the last frames of the output are held back, enough for the response of
+/// a segment at any of the input sample rates given when creating the Concatenator.
+/// The output is delivered in chunks of a fixed size that run across the segment boundaries.
+pub struct Concatenator<T> {
+    fs_out: usize,
+    chunk_size: usize,
+    nbr_channels: usize,
+    resampler_type: SegmentResampler,
+    resampler: Option<Box<dyn VecResampler<T>>>,
+    fs_in: usize,
+    input_buffer: Vec<Vec<T>>,
+    output_buffer: Vec<Vec<T>>,
+    write_pos: usize,
+    frames_in: usize,
+    frames_out: usize,
+    frames_to_skip: usize,
+    frames_before_start: usize,
+    max_lead_len: usize,
+    tail_len: usize,
+}
+
+impl<T> Concatenator<T>
+where
+    T: Sample,
+{
+    /// Create a new Concatenator
+    ///
+    /// Parameters are:
+    /// - `fs_out`: Output sample rate.
+    /// - `resampler_type`: The type of resampler to use for the segments.
+    /// - `chunk_size`: size of output chunks in frames.
+    /// - `nbr_channels`: number of channels in input/output.
+    /// - `rates_in`: The input sample rates that the segments may have.
+    ///   The output is held back by the longest filter response of these rates.
+    ///   Segments at other rates can also be used, as long as their response is not longer.
+    pub fn new(
+        fs_out: usize,
+        resampler_type: SegmentResampler,
+        chunk_size: usize,
+        nbr_channels: usize,
+        rates_in: &[usize],
+    ) -> Self {
+        debug!(
+            "Create new Concatenator, fs_out: {}, type: {:?}, chunk_size: {}, channels: {}, rates_in: {:?}",
+            fs_out, resampler_type, chunk_size, nbr_channels, rates_in
+        );
+        let max_lead_len = rates_in
+            .iter()
+            .map(|fs_in| {
+                let (_, lead_len, _) = make_segment_resampler::<T>(
+                    &resampler_type,
+                    *fs_in,
+                    fs_out,
+                    chunk_size,
+                    nbr_channels,
+                );
+                lead_len
+            })
+            .max()
+            .unwrap_or(0);
+        Concatenator {
+            fs_out,
+            chunk_size,
+            nbr_channels,
+            resampler_type,
+            resampler: None,
+            fs_in: fs_out,
+            input_buffer: vec![Vec::new(); nbr_channels],
+            output_buffer: vec![Vec::new(); nbr_channels],
+            write_pos: 0,
+            frames_in: 0,
+            frames_out: 0,
+            frames_to_skip: 0,
+            frames_before_start: 0,
+            max_lead_len,
+            tail_len: 0,
+        }
+    }
+
+    /// Start a new segment with the given input sample rate.
+    /// The current segment, if any, is finished first.
+    /// # Errors
+    ///
+    /// The function returns an error if the filter response of the new segment reaches further
+    /// back than for any of the input sample rates given when creating the Concatenator.
+    pub fn start_segment(&mut self, fs_in: usize) -> ResampleResult<()> {
+        // The resampler output starts with `lead_len` frames before the start of the segment,
+        // and keeps going for `tail_len` frames after the end.
+        let (resampler, lead_len, tail_len) = make_segment_resampler(
+            &self.resampler_type,
+            fs_in,
+            self.fs_out,
+            self.chunk_size,
+            self.nbr_channels,
+        );
+        if lead_len > self.max_lead_len {
+            return Err(ResampleError::BadSegmentRate { fs_in });
+        }
+        self.finish_segment()?;
+        debug!("Start new segment, fs_in: {}", fs_in);
+        // The output is held back by at least `lead_len` frames, except at the start
+        // of the stream, where the part of the response before the start is dropped.
+        self.frames_before_start = lead_len.min(self.write_pos);
+        self.frames_to_skip = lead_len - self.frames_before_start;
+        self.write_pos -= self.frames_before_start;
+        self.resampler = Some(resampler);
+        self.tail_len = tail_len;
+        self.fs_in = fs_in;
+        self.frames_in = 0;
+        self.frames_out = 0;
+        Ok(())
+    }
+
+    /// Add audio to the current segment. The input may have any number of frames,
+    /// but all channels must have the same length.
+    /// Resampled audio is stored until it is read with `read_chunk`.
+    /// If no segment has been started, a segment at the output sample rate is started.
+    /// # Errors
+    ///
+    /// The function returns an error if the number of channels is not equal to the number
+    /// defined when creating the instance, or if the channels have different lengths.
+    /// Starting a segment at the output sample rate returns an error if that rate
+    /// needs more held back output than the input sample rates of the Concatenator.
+    pub fn write<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<()> {
+        if wave_in.len() != self.nbr_channels {
+            return Err(ResampleError::WrongNumberOfChannels {
+                expected: self.nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        let frames = wave_in.first().map(|wave| wave.as_ref().len()).unwrap_or(0);
+        for (chan, wave) in wave_in.iter().enumerate() {
+            if wave.as_ref().len() != frames {
+                return Err(ResampleError::WrongNumberOfFrames {
+                    channel: chan,
+                    expected: frames,
+                    actual: wave.as_ref().len(),
+                });
+            }
+        }
+        if self.resampler.is_none() {
+            self.start_segment(self.fs_out)?;
+        }
+        for (buffer, wave) in self.input_buffer.iter_mut().zip(wave_in.iter()) {
+            buffer.extend_from_slice(wave.as_ref());
+        }
+        self.frames_in += frames;
+        self.process_input(usize::MAX)
+    }
+
+    /// Get the next chunk of output, if there is enough resampled audio available.
+    pub fn read_chunk(&mut self) -> Option<Vec<Vec<T>>> {
+        let chunk_size = self.chunk_size;
+        // hold back the frames that the start of a following segment may add to
+        if self.write_pos < chunk_size + self.max_lead_len {
+            return None;
+        }
+        self.write_pos -= chunk_size;
+        Some(
+            self.output_buffer
+                .iter_mut()
+                .map(|buffer| buffer.drain(..chunk_size).collect())
+                .collect(),
+        )
+    }
+
+    /// Finish the current segment, and return all remaining output.
+    /// The last chunk is usually shorter than the chunk size.
+    pub fn finish(&mut self) -> ResampleResult<Vec<Vec<T>>> {
+        self.finish_segment()?;
+        let frames = self.write_pos;
+        self.write_pos = 0;
+        Ok(self
+            .output_buffer
+            .iter_mut()
+            .map(|buffer| {
+                // the tail of the last segment is past the end of the output
+                buffer.truncate(frames);
+                std::mem::take(buffer)
+            })
+            .collect())
+    }
+
+    /// Get the number of output frames that the current segment will produce in total,
+    /// based on the input so far, including the frames before the start.
+    fn expected_frames_out(&self) -> usize {
+        self.frames_before_start
+            + ((self.frames_in as f64 * self.fs_out as f64 / self.fs_in as f64).round()) as usize
+    }
+
+    /// Run the resampler as long as there is enough input, and until
+    /// at most `max_frames_out` output frames have been produced for the segment.
+    /// The output is added to what is already in the output buffer at the write position,
+    /// which may be the tail of the previous segment.
+    fn process_input(&mut self, max_frames_out: usize) -> ResampleResult<()> {
+        let resampler = match self.resampler.as_mut() {
+            Some(resampler) => resampler,
+            None => return Ok(()),
+        };
+        while self.frames_out < max_frames_out {
+            let needed = resampler.nbr_frames_needed();
+            if self.input_buffer[0].len() < needed {
+                break;
+            }
+            let wave_in: Vec<Vec<T>> = self
+                .input_buffer
+                .iter_mut()
+                .map(|buffer| buffer.drain(..needed).collect())
+                .collect();
+            let wave_out = resampler.process(&wave_in)?;
+            let skip = self.frames_to_skip.min(wave_out[0].len());
+            self.frames_to_skip -= skip;
+            let keep = (wave_out[0].len() - skip).min(max_frames_out - self.frames_out);
+            for (buffer, wave) in self.output_buffer.iter_mut().zip(wave_out.iter()) {
+                let end = self.write_pos + keep;
+                if buffer.len() < end {
+                    buffer.resize(end, T::zero());
+                }
+                for (out, value) in buffer[self.write_pos..end]
+                    .iter_mut()
+                    .zip(wave[skip..skip + keep].iter())
+                {
+                    *out += *value;
+                }
+            }
+            self.write_pos += keep;
+            self.frames_out += keep;
+        }
+        Ok(())
+    }
+
+    /// Run the resampler of the current segment on silence after the input,
+    /// until the segment has produced `frames_out` output frames.
+    fn flush(&mut self, frames_out: usize) -> ResampleResult<()> {
+        while self.frames_out < frames_out {
+            let needed = self.resampler.as_ref().unwrap().nbr_frames_needed();
+            for buffer in self.input_buffer.iter_mut() {
+                if buffer.len() < needed {
+                    buffer.resize(needed, T::zero());
+                }
+            }
+            self.process_input(frames_out)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the resampler of the current segment with silence, until the segment
+    /// has produced its expected number of output frames followed by its tail.
+    /// The write position is left at the end of the segment, where the next segment starts.
+    fn finish_segment(&mut self) -> ResampleResult<()> {
+        if self.resampler.is_none() {
+            return Ok(());
+        }
+        let expected = self.expected_frames_out();
+        trace!(
+            "Finish segment, {} frames in, {} frames out",
+            self.frames_in,
+            expected - self.frames_before_start
+        );
+        let segment_end = self.write_pos + expected - self.frames_out;
+        self.flush(expected + self.tail_len)?;
+        self.write_pos = segment_end;
+        for buffer in self.input_buffer.iter_mut() {
+            buffer.clear();
+        }
+        self.resampler = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Concatenator, InterpolationParameters, InterpolationType, SegmentResampler, WindowFunction,
+    };
+
+    fn sine(len: usize, freq: f64, fs: usize) -> Vec<f64> {
+        (0..len)
+            .map(|n| (2.0 * std::f64::consts::PI * freq * n as f64 / fs as f64).sin())
+            .collect()
+    }
+
+    fn sinc_params() -> InterpolationParameters {
+        InterpolationParameters {
+            sinc_len: 128,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+
+    const SEGMENTS: [(usize, usize); 3] = [(44100, 10000), (48000, 7000), (96000, 3001)];
+
+    fn read_chunks(
+        concat: &mut Concatenator<f64>,
+        lengths: &mut Vec<usize>,
+        output: &mut Vec<f64>,
+    ) {
+        while let Some(out) = concat.read_chunk() {
+            lengths.push(out[0].len());
+            output.extend_from_slice(&out[0]);
+        }
+    }
+
+    // Resample a sine in each segment, reading the output after each write
+    // or only at the end.
+    fn concatenate(
+        resampler_type: SegmentResampler,
+        segments: &[(usize, usize)],
+        chunk_size: usize,
+        read_while_writing: bool,
+    ) -> (Vec<usize>, Vec<f64>) {
+        let rates_in: Vec<usize> = segments.iter().map(|(fs_in, _)| *fs_in).collect();
+        let mut concat = Concatenator::<f64>::new(48000, resampler_type, chunk_size, 1, &rates_in);
+        let mut lengths = Vec::new();
+        let mut output = Vec::new();
+        for (fs_in, len) in segments.iter() {
+            concat.start_segment(*fs_in).unwrap();
+            let wave = sine(*len, 200.0, *fs_in);
+            for chunk in wave.chunks(1000) {
+                concat.write(&[chunk]).unwrap();
+                if read_while_writing {
+                    read_chunks(&mut concat, &mut lengths, &mut output);
+                }
+            }
+        }
+        read_chunks(&mut concat, &mut lengths, &mut output);
+        let last = concat.finish().unwrap();
+        lengths.push(last[0].len());
+        output.extend_from_slice(&last[0]);
+        (lengths, output)
+    }
+
+    #[test]
+    fn sinc_segments() {
+        let (lengths, output) =
+            concatenate(SegmentResampler::Sinc(sinc_params()), &SEGMENTS, 512, true);
+        // 10000 frames at 44.1 kHz give 10884 frames, and 3001 frames at 96 kHz give 1501
+        assert_eq!(output.len(), 10884 + 7000 + 1501);
+        for len in lengths.iter().take(lengths.len() - 1) {
+            assert_eq!(*len, 512);
+        }
+        // Each segment starts at its own start, without gaps or overlaps.
+        // The sine restarts at each segment, skip the ringing around the joins.
+        let starts = [0, 10884, 17884];
+        let ends = [10884, 17884, 19385];
+        for (start, end) in starts.iter().zip(ends.iter()) {
+            for (n, val) in output.iter().enumerate().take(end - 100).skip(start + 100) {
+                let expected =
+                    (2.0 * std::f64::consts::PI * 200.0 * (n - start) as f64 / 48000.0).sin();
+                assert!(
+                    (val - expected).abs() < 1.0e-6,
+                    "frame {}, got {}, expected {}",
+                    n,
+                    val,
+                    expected
+                );
+            }
+        }
+    }
+
+    // Resample a sine that continues across the segments, which all have the same sample rate,
+    // and return the largest error compared to the expected sine.
+    fn continuous_error(resampler_type: SegmentResampler, fs_in: usize) -> f64 {
+        let mut concat = Concatenator::<f64>::new(48000, resampler_type, 512, 1, &[fs_in]);
+        let wave = sine(14700 + 7350 + 2940, 200.0, fs_in);
+        let mut output = Vec::new();
+        for segment in [&wave[..7350], &wave[7350..22050], &wave[22050..]].iter() {
+            concat.start_segment(fs_in).unwrap();
+            for chunk in segment.chunks(1000) {
+                concat.write(&[chunk]).unwrap();
+                while let Some(out) = concat.read_chunk() {
+                    output.extend_from_slice(&out[0]);
+                }
+            }
+        }
+        output.extend_from_slice(&concat.finish().unwrap()[0]);
+        let frames_out = (wave.len() * 48000) / fs_in;
+        assert_eq!(output.len(), frames_out);
+        let expected = sine(frames_out, 200.0, 48000);
+        // skip the start and end, where the signal starts and ends abruptly
+        output
+            .iter()
+            .zip(expected.iter())
+            .skip(500)
+            .take(frames_out - 1000)
+            .fold(0.0, |acc, (val, exp)| acc.max((val - exp).abs()))
+    }
+
+    #[test]
+    fn sinc_continuous() {
+        let error = continuous_error(SegmentResampler::Sinc(sinc_params()), 44100);
+        assert!(error < 1.0e-6, "error {}", error);
+        let error = continuous_error(SegmentResampler::Sinc(sinc_params()), 48000);
+        assert!(error < 1.0e-6, "error {}", error);
+    }
+
+    #[test]
+    fn fft_continuous() {
+        let error = continuous_error(SegmentResampler::Fft(2), 44100);
+        assert!(error < 1.0e-6, "error {}", error);
+        let error = continuous_error(SegmentResampler::Fft(2), 48000);
+        assert!(error < 1.0e-6, "error {}", error);
+    }
+
+    #[test]
+    fn fft_segments() {
+        let (lengths, output) = concatenate(SegmentResampler::Fft(2), &SEGMENTS, 512, true);
+        assert_eq!(output.len(), 10884 + 7000 + 1501);
+        for len in lengths.iter().take(lengths.len() - 1) {
+            assert_eq!(*len, 512);
+        }
+    }
+
+    // The response of the second segment reaches further back than that of the first one,
+    // and further than what a small chunk size leaves unread at the join.
+    // Reading the output while writing must not drop any of it.
+    const GROWING_LEAD: [(usize, usize); 3] = [(192000, 6000), (8000, 1000), (44100, 5000)];
+
+    #[test]
+    fn sinc_growing_lead() {
+        let resampler_type = SegmentResampler::Sinc(sinc_params());
+        let (_, eager) = concatenate(resampler_type.clone(), &GROWING_LEAD, 64, true);
+        let (_, deferred) = concatenate(resampler_type, &GROWING_LEAD, 64, false);
+        assert_eq!(eager, deferred);
+    }
+
+    #[test]
+    fn fft_growing_lead() {
+        let (_, eager) = concatenate(SegmentResampler::Fft(2), &GROWING_LEAD, 64, true);
+        let (_, deferred) = concatenate(SegmentResampler::Fft(2), &GROWING_LEAD, 64, false);
+        assert_eq!(eager, deferred);
+    }
+
+    #[test]
+    fn bad_segment_rate() {
+        let resampler_type = SegmentResampler::Sinc(sinc_params());
+        let mut concat = Concatenator::<f64>::new(48000, resampler_type, 512, 1, &[44100, 48000]);
+        assert!(concat.start_segment(48000).is_ok());
+        assert!(concat.start_segment(96000).is_ok());
+        assert!(concat.start_segment(22050).is_err());
+    }
+
+    #[test]
+    fn wrong_channels() {
+        let mut concat =
+            Concatenator::<f64>::new(48000, SegmentResampler::Fft(1), 512, 2, &[48000]);
+        assert!(concat.write(&[vec![0.0; 10]]).is_err());
+        assert!(concat.write(&[vec![0.0; 10], vec![0.0; 11]]).is_err());
+        assert!(concat.write(&[vec![0.0; 10], vec![0.0; 10]]).is_ok());
+    }
+}
@@ -0,0 +1,320 @@
+use crate::asynchro::{interpolate_block, make_interpolator, SincInterpolator};
+use crate::error::{ResampleError, ResampleResult};
+use crate::{InterpolationParameters, InterpolationType, Sample};
+
+/// A fractional delay line, delaying each channel by an arbitrary number of frames.
+///
+/// The delayed samples are calculated with the same sinc interpolation as the asynchronous
+/// resamplers, at the original sample rate. The delay of each channel can be changed
+/// at any time, and changes are ramped smoothly over the next chunk.
+/// This makes it possible to automate the delay, for example for Doppler effects.
+///
+/// The sinc interpolation adds a fixed latency, given by [FractionalDelay::output_delay],
+/// that is the same for all channels.
+pub struct FractionalDelay<T> {
+    nbr_channels: usize,
+    chunk_size: usize,
+    max_delay: usize,
+    interpolator: Box<dyn SincInterpolator<T>>,
+    interpolation: InterpolationType,
+    buffer: Vec<Vec<T>>,
+    delays: Vec<f64>,
+    target_delays: Vec<f64>,
+}
+
+impl<T> FractionalDelay<T>
+where
+    T: Sample,
+{
+    /// Create a new FractionalDelay
+    ///
+    /// Parameters are:
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`.
+    /// - `max_delay`: The largest delay that can be used, in frames.
+    /// - `chunk_size`: size of input and output data in frames.
+    /// - `nbr_channels`: number of channels in input/output.
+    pub fn new(
+        parameters: InterpolationParameters,
+        max_delay: usize,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new FractionalDelay, max_delay: {}, chunk_size: {}, channels: {}, parameters: {:?}",
+            max_delay, chunk_size, nbr_channels, parameters
+        );
+        let interpolator = make_interpolator(
+            parameters.sinc_len,
+            1.0,
+            parameters.f_cutoff,
+            parameters.oversampling_factor,
+            parameters.window,
+        );
+        Self::new_with_interpolator(
+            parameters.interpolation,
+            interpolator,
+            max_delay,
+            chunk_size,
+            nbr_channels,
+        )
+    }
+
+    /// Create a new FractionalDelay using an existing Interpolator
+    ///
+    /// Parameters are:
+    /// - `interpolation_type`: Parameters for interpolation, see `InterpolationParameters`.
+    /// - `interpolator`: The interpolator to use.
+    /// - `max_delay`: The largest delay that can be used, in frames.
+    /// - `chunk_size`: size of input and output data in frames.
+    /// - `nbr_channels`: number of channels in input/output.
+    pub fn new_with_interpolator(
+        interpolation_type: InterpolationType,
+        interpolator: Box<dyn SincInterpolator<T>>,
+        max_delay: usize,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        let buffer_len = max_delay + interpolator.len() + 2 + chunk_size;
+        FractionalDelay {
+            nbr_channels,
+            chunk_size,
+            max_delay,
+            interpolator,
+            interpolation: interpolation_type,
+            buffer: vec![vec![T::zero(); buffer_len]; nbr_channels],
+            delays: vec![0.0; nbr_channels],
+            target_delays: vec![0.0; nbr_channels],
+        }
+    }
+
+    /// Set the delay of a channel, in frames.
+    /// The delay changes gradually from the current value to the new one
+    /// during the next call to `process`.
+    /// # Errors
+    ///
+    /// The function returns an error if the channel doesn't exist, or if the delay is negative
+    /// or larger than the maximum delay defined when creating the instance.
+    pub fn set_delay(&mut self, channel: usize, delay: f64) -> ResampleResult<()> {
+        self.check_channel(channel)?;
+        if !(0.0..=self.max_delay as f64).contains(&delay) {
+            return Err(ResampleError::BadDelay {
+                channel,
+                delay,
+                max_delay: self.max_delay,
+            });
+        }
+        self.target_delays[channel] = delay;
+        Ok(())
+    }
+
+    /// Get the delay that a channel will have at the end of the next chunk, in frames.
+    /// # Errors
+    ///
+    /// The function returns an error if the channel doesn't exist.
+    pub fn delay(&self, channel: usize) -> ResampleResult<f64> {
+        self.check_channel(channel)?;
+        Ok(self.target_delays[channel])
+    }
+
+    /// Check that a channel exists.
+    fn check_channel(&self, channel: usize) -> ResampleResult<()> {
+        if channel >= self.nbr_channels {
+            return Err(ResampleError::BadChannel {
+                channel,
+                nbr_channels: self.nbr_channels,
+            });
+        }
+        Ok(())
+    }
+
+    /// Delay a chunk of audio. The output has the same length as the input.
+    /// If the waveform for a channel is empty, this channel will be ignored and produce a
+    /// corresponding empty output waveform.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input data is not equal
+    /// to the number of channels and chunk size defined when creating the instance.
+    pub fn process<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<Vec<Vec<T>>> {
+        if wave_in.len() != self.nbr_channels {
+            return Err(ResampleError::WrongNumberOfChannels {
+                expected: self.nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        let mut used_channels = Vec::new();
+        for (chan, wave) in wave_in.iter().enumerate() {
+            let wave = wave.as_ref();
+            if !wave.is_empty() {
+                used_channels.push(chan);
+                if wave.len() != self.chunk_size {
+                    return Err(ResampleError::WrongNumberOfFrames {
+                        channel: chan,
+                        expected: self.chunk_size,
+                        actual: wave.len(),
+                    });
+                }
+            }
+        }
+        let sinc_len = self.interpolator.len();
+        let oversampling_factor = self.interpolator.nbr_sincs();
+        let history_len = self.buffer[0].len() - self.chunk_size;
+        // Offset from the time of an input sample to the time used by the interpolator.
+        let offset = self.output_delay() as f64 + (sinc_len / 2) as f64 - 1.0
+            + 1.0 / oversampling_factor as f64;
+
+        let mut wave_out = vec![Vec::new(); self.nbr_channels];
+        for &chan in used_channels.iter() {
+            let buf = &mut self.buffer[chan];
+            buf.copy_within(self.chunk_size.., 0);
+            buf[history_len..].copy_from_slice(wave_in[chan].as_ref());
+
+            let start_delay = self.delays[chan];
+            let step = (self.target_delays[chan] - start_delay) / self.chunk_size as f64;
+            // The interpolation adds 2 * sinc_len frames to the times,
+            // for the history kept in the buffers of the resamplers.
+            let times: Vec<f64> = (0..self.chunk_size)
+                .map(|n| {
+                    let delay = start_delay + step * (n + 1) as f64;
+                    (history_len + n) as f64 - delay - offset - (2 * sinc_len) as f64
+                })
+                .collect();
+            let mut out = vec![T::zero(); self.chunk_size];
+            interpolate_block(
+                &*self.interpolator,
+                &self.interpolation,
+                &[&buf[..]],
+                &times,
+                |_, n, value| out[n] = value,
+            );
+            self.delays[chan] = self.target_delays[chan];
+            wave_out[chan] = out;
+        }
+        Ok(wave_out)
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    /// Will always return the chunk_size defined when creating the instance.
+    pub fn nbr_frames_needed(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Get the fixed latency of the delay line in frames, that is added to the delay
+    /// of every channel.
+    pub fn output_delay(&self) -> usize {
+        self.interpolator.len() / 2 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FractionalDelay, InterpolationParameters, InterpolationType, WindowFunction};
+
+    fn params(interpolation: InterpolationType) -> InterpolationParameters {
+        InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+
+    fn sine(start: usize, len: usize, omega: f64) -> Vec<f64> {
+        (start..start + len)
+            .map(|n| (omega * n as f64).sin())
+            .collect()
+    }
+
+    fn check_delays(interpolation: InterpolationType) {
+        let mut delay = FractionalDelay::<f64>::new(params(interpolation), 20, 128, 3);
+        let delays = [0.0, 2.5, 17.3];
+        for (chan, d) in delays.iter().enumerate() {
+            delay.set_delay(chan, *d).unwrap();
+        }
+        let latency = delay.output_delay() as f64;
+        let omega = 0.2;
+        let mut output = vec![Vec::new(); 3];
+        for chunk in 0..8 {
+            let wave = sine(128 * chunk, 128, omega);
+            let out = delay.process(&[&wave, &wave, &wave]).unwrap();
+            for (chan, o) in out.iter().enumerate() {
+                output[chan].extend_from_slice(o);
+            }
+        }
+        for (chan, d) in delays.iter().enumerate() {
+            for (n, val) in output[chan].iter().enumerate().skip(200) {
+                let expected = (omega * (n as f64 - latency - d)).sin();
+                assert!(
+                    (val - expected).abs() < 1.0e-3,
+                    "chan {}, frame {}, got {}, expected {}",
+                    chan,
+                    n,
+                    val,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn delays_cubic() {
+        check_delays(InterpolationType::Cubic);
+    }
+
+    #[test]
+    fn delays_linear() {
+        check_delays(InterpolationType::Linear);
+    }
+
+    #[test]
+    fn delays_quintic() {
+        check_delays(InterpolationType::Quintic);
+    }
+
+    #[test]
+    fn ramp_delay() {
+        let mut delay = FractionalDelay::<f64>::new(params(InterpolationType::Cubic), 20, 128, 1);
+        let latency = delay.output_delay() as f64;
+        let omega = 0.05;
+        let mut output = Vec::new();
+        for chunk in 0..4 {
+            if chunk == 2 {
+                delay.set_delay(0, 10.0).unwrap();
+            }
+            let out = delay.process(&[sine(128 * chunk, 128, omega)]).unwrap();
+            output.extend_from_slice(&out[0]);
+        }
+        // during the ramp, the delay increases by 10/128 frames for each frame
+        for (n, val) in output.iter().enumerate().skip(256).take(128) {
+            let d = 10.0 * (n - 255) as f64 / 128.0;
+            let expected = (omega * (n as f64 - latency - d)).sin();
+            assert!((val - expected).abs() < 1.0e-3);
+        }
+        assert_eq!(delay.delay(0).unwrap(), 10.0);
+    }
+
+    #[test]
+    fn bad_delay() {
+        let mut delay = FractionalDelay::<f32>::new(params(InterpolationType::Linear), 20, 64, 2);
+        assert!(delay.set_delay(1, 20.0).is_ok());
+        assert!(delay.set_delay(1, 20.5).is_err());
+        assert!(delay.set_delay(0, -0.5).is_err());
+    }
+
+    #[test]
+    fn bad_channel() {
+        let mut delay = FractionalDelay::<f32>::new(params(InterpolationType::Linear), 20, 64, 2);
+        assert!(delay.set_delay(2, 1.0).is_err());
+        assert!(delay.delay(2).is_err());
+        assert_eq!(delay.delay(1).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn skipped_and_wrong_frames() {
+        let mut delay = FractionalDelay::<f64>::new(params(InterpolationType::Cubic), 8, 64, 2);
+        let out = delay.process(&[vec![0.0; 64], Vec::new()]).unwrap();
+        assert_eq!(out[0].len(), 64);
+        assert!(out[1].is_empty());
+        assert!(delay.process(&[vec![0.0; 63], vec![0.0; 64]]).is_err());
+    }
+}
@@ -13,9 +13,15 @@ pub enum CpuFeature {
     /// the fma cpu feature.
     #[cfg(target_arch = "x86_64")]
     Fma,
+    /// x86_64 avx512f cpu feature.
+    #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+    Avx512f,
     /// aarc64 neon cpu feature.
     #[cfg(all(feature = "neon", target_arch = "aarch64"))]
     Neon,
+    /// wasm32 simd128 feature.
+    #[cfg(all(feature = "simd128", target_arch = "wasm32"))]
+    Simd128,
 }
 
 impl CpuFeature {
@@ -34,10 +40,19 @@ impl CpuFeature {
             CpuFeature::Fma => {
                 is_x86_feature_detected!("fma")
             }
+            #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+            CpuFeature::Avx512f => {
+                is_x86_feature_detected!("avx512f")
+            }
             #[cfg(all(feature = "neon", target_arch = "aarch64"))]
             CpuFeature::Neon => {
                 is_aarch64_feature_detected!("neon")
             }
+            // WebAssembly has no runtime detection, simd128 must be enabled when compiling.
+            #[cfg(all(feature = "simd128", target_arch = "wasm32"))]
+            CpuFeature::Simd128 => {
+                cfg!(target_feature = "simd128")
+            }
         }
     }
 }
@@ -58,10 +73,18 @@ impl fmt::Display for CpuFeature {
             CpuFeature::Fma => {
                 write!(f, "fma")
             }
+            #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+            CpuFeature::Avx512f => {
+                write!(f, "avx512f")
+            }
             #[cfg(all(feature = "neon", target_arch = "aarch64"))]
             CpuFeature::Neon => {
                 write!(f, "neon")
             }
+            #[cfg(all(feature = "simd128", target_arch = "wasm32"))]
+            CpuFeature::Simd128 => {
+                write!(f, "simd128")
+            }
         }
     }
 }
@@ -86,6 +109,30 @@ pub enum ResampleError {
     BadRatioUpdate,
     /// Error raised when trying to adjust a synchronous resampler.
     SyncNotAdjustable,
+    /// Error raised when a ratio curve is empty, or has breakpoints that are not
+    /// in increasing order within the range 0 to 1.
+    BadRatioCurve,
+    /// Error raised when FractionalDelay::set_delay is called with a delay
+    /// that is negative or larger than the maximum.
+    BadDelay {
+        channel: usize,
+        delay: f64,
+        max_delay: usize,
+    },
+    /// Error raised when SincFixedOut::reconfigure is called with a crossfade that is
+    /// longer than the output the resampler can make from its buffered input.
+    BadCrossfade {
+        crossfade_len: usize,
+        max_crossfade_len: usize,
+    },
+    /// Error raised when a channel index is not smaller than the number of channels.
+    BadChannel { channel: usize, nbr_channels: usize },
+    /// Error raised when the taper given to `resample_whole_fft`
+    /// is outside the range 0 to 1.
+    BadTaper { taper: f64 },
+    /// Error raised when a Concatenator segment has a sample rate whose filter response
+    /// reaches further back than the Concatenator holds back its output for.
+    BadSegmentRate { fs_in: usize },
     /// Error raised when the number of input channels doesn't match expected.
     WrongNumberOfChannels { expected: usize, actual: usize },
     /// Error raised when the number of frames in a single input channel doesn't match
@@ -116,6 +163,50 @@ impl fmt::Display for ResampleError {
             Self::SyncNotAdjustable { .. } => {
                 write!(f, "Not possible to adjust a synchronous resampler")
             }
+            Self::BadRatioCurve => {
+                write!(f, "Ratio curve breakpoints must be in order from 0 to 1")
+            }
+            Self::BadDelay {
+                channel,
+                delay,
+                max_delay,
+            } => {
+                write!(
+                    f,
+                    "Delay {} for channel {} is outside the allowed range 0 to {}",
+                    delay, channel, max_delay
+                )
+            }
+            Self::BadCrossfade {
+                crossfade_len,
+                max_crossfade_len,
+            } => {
+                write!(
+                    f,
+                    "Crossfade of {} frames is longer than the maximum of {}",
+                    crossfade_len, max_crossfade_len
+                )
+            }
+            Self::BadChannel {
+                channel,
+                nbr_channels,
+            } => {
+                write!(
+                    f,
+                    "Channel {} is out of range, the number of channels is {}",
+                    channel, nbr_channels
+                )
+            }
+            Self::BadTaper { taper } => {
+                write!(f, "Taper {} is outside the allowed range 0 to 1", taper)
+            }
+            Self::BadSegmentRate { fs_in } => {
+                write!(
+                    f,
+                    "Segments at {} Hz need more held back output than the Concatenator has",
+                    fs_in
+                )
+            }
             Self::WrongNumberOfChannels { expected, actual } => {
                 write!(
                     f,
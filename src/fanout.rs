@@ -0,0 +1,278 @@
+use crate::asynchro::{
+    interpolate_channels_serial, make_interpolator, sinc_output_delay, SincInterpolator,
+};
+use crate::error::{ResampleError, ResampleResult};
+use crate::{InterpolationParameters, InterpolationType, Sample};
+
+/// The state of one output rate of a [SincFanOut].
+struct FanOutTarget {
+    resample_ratio: f64,
+    interpolator: usize,
+    last_index: f64,
+}
+
+/// An asynchronous resampler that converts one input stream to several output sample rates.
+///
+/// This gives the same result as using one [crate::SincFixedIn] per output rate,
+/// but the input is buffered only once, and the sinc tables are shared between
+/// the output rates that use the same cutoff frequency.
+/// All output rates above the input rate use the same table.
+pub struct SincFanOut<T> {
+    nbr_channels: usize,
+    chunk_size: usize,
+    interpolation: InterpolationType,
+    interpolators: Vec<Box<dyn SincInterpolator<T>>>,
+    targets: Vec<FanOutTarget>,
+    buffer: Vec<Vec<T>>,
+}
+
+impl<T> SincFanOut<T>
+where
+    T: Sample,
+{
+    /// Create a new SincFanOut
+    ///
+    /// Parameters are:
+    /// - `resample_ratios`: Ratios between output and input sample rates, one per output.
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
+    /// - `chunk_size`: size of input data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    pub fn new(
+        resample_ratios: &[f64],
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new SincFanOut, ratios: {:?}, chunk_size: {}, channels: {}, parameters: {:?}",
+            resample_ratios, chunk_size, nbr_channels, parameters
+        );
+        let mut interpolators: Vec<Box<dyn SincInterpolator<T>>> = Vec::new();
+        let mut cutoffs = Vec::new();
+        let mut targets = Vec::new();
+        for ratio in resample_ratios.iter() {
+            let cutoff = ratio.min(1.0);
+            let interpolator = match cutoffs.iter().position(|c| *c == cutoff) {
+                Some(idx) => idx,
+                None => {
+                    interpolators.push(make_interpolator(
+                        parameters.sinc_len,
+                        *ratio,
+                        parameters.f_cutoff,
+                        parameters.oversampling_factor,
+                        parameters.window,
+                    ));
+                    cutoffs.push(cutoff);
+                    interpolators.len() - 1
+                }
+            };
+            targets.push(FanOutTarget {
+                resample_ratio: *ratio,
+                interpolator,
+                last_index: -((interpolators[interpolator].len() / 2) as f64),
+            });
+        }
+        trace!(
+            "Using {} sinc tables for {} outputs",
+            interpolators.len(),
+            targets.len()
+        );
+        let sinc_len = interpolators.first().map(|i| i.len()).unwrap_or(0);
+        let buffer = vec![vec![T::zero(); chunk_size + 2 * sinc_len]; nbr_channels];
+        SincFanOut {
+            nbr_channels,
+            chunk_size,
+            interpolation: parameters.interpolation,
+            interpolators,
+            targets,
+            buffer,
+        }
+    }
+
+    /// Resample a chunk of audio to all output rates.
+    /// The input length is fixed, and the outputs vary in length.
+    /// The result has one entry per output rate, in the order given when creating the instance,
+    /// and each entry uses the same layout as the output of [crate::Resampler::process].
+    /// If the waveform for a channel is empty, this channel will be ignored and produce a
+    /// corresponding empty output waveform for every output rate.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input data is not equal
+    /// to the number of channels and chunk size defined when creating the instance.
+    pub fn process<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<Vec<Vec<Vec<T>>>> {
+        if wave_in.len() != self.nbr_channels {
+            return Err(ResampleError::WrongNumberOfChannels {
+                expected: self.nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        let mut used_channels = Vec::new();
+        for (chan, wave) in wave_in.iter().enumerate() {
+            let wave = wave.as_ref();
+            if !wave.is_empty() {
+                used_channels.push(chan);
+                if wave.len() != self.chunk_size {
+                    return Err(ResampleError::WrongNumberOfFrames {
+                        channel: chan,
+                        expected: self.chunk_size,
+                        actual: wave.len(),
+                    });
+                }
+            }
+        }
+        let history = self.buffer[0].len() - self.chunk_size;
+        //update buffer with new data, once for all outputs
+        for buf in self.buffer.iter_mut() {
+            buf.copy_within(self.chunk_size.., 0);
+        }
+        for chan in used_channels.iter() {
+            self.buffer[*chan][history..].copy_from_slice(wave_in[*chan].as_ref());
+        }
+        let mut waves_out = Vec::with_capacity(self.targets.len());
+        for target in self.targets.iter_mut() {
+            let interpolator = self.interpolators[target.interpolator].as_ref();
+            let t_ratio = 1.0 / target.resample_ratio;
+            let end_idx = self.chunk_size as isize
+                - (interpolator.len() as isize + 1)
+                - t_ratio.ceil() as isize;
+            let mut times = Vec::with_capacity(
+                (self.chunk_size as f64 * target.resample_ratio + 10.0) as usize,
+            );
+            let mut idx = target.last_index;
+            while idx < end_idx as f64 {
+                idx += t_ratio;
+                times.push(idx);
+            }
+            let mut wave_out = vec![Vec::new(); self.nbr_channels];
+            for chan in used_channels.iter() {
+                wave_out[*chan] = vec![T::zero(); times.len()];
+            }
+            interpolate_channels_serial(
+                interpolator,
+                &self.interpolation,
+                &self.buffer,
+                &used_channels,
+                &times,
+                &mut wave_out,
+            );
+            target.last_index = idx - self.chunk_size as f64;
+            waves_out.push(wave_out);
+        }
+        trace!(
+            "Resampling channels {:?}, {} frames in, to {} outputs",
+            used_channels,
+            self.chunk_size,
+            waves_out.len()
+        );
+        Ok(waves_out)
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    /// Will always return the chunk_size defined when creating the instance.
+    pub fn nbr_frames_needed(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Get the number of output rates.
+    pub fn nbr_outputs(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Get the delay in frames of one of the outputs, see [crate::Resampler::output_delay].
+    pub fn output_delay(&self, output: usize) -> usize {
+        let target = &self.targets[output];
+        sinc_output_delay(
+            target.resample_ratio,
+            self.interpolators[target.interpolator].nbr_sincs(),
+        )
+        .round()
+        .max(0.0) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        InterpolationParameters, InterpolationType, Resampler, SincFanOut, SincFixedIn,
+        WindowFunction,
+    };
+
+    fn params(interpolation: InterpolationType) -> InterpolationParameters {
+        InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+
+    fn compare_with_fixed_in(interpolation: InterpolationType) {
+        let ratios = [44100.0 / 32000.0, 48000.0 / 32000.0, 16000.0 / 32000.0];
+        let mut fanout = SincFanOut::<f64>::new(&ratios, params(interpolation.clone()), 1024, 2);
+        assert_eq!(fanout.nbr_outputs(), 3);
+        let mut singles: Vec<SincFixedIn<f64>> = ratios
+            .iter()
+            .map(|ratio| SincFixedIn::new(*ratio, params(interpolation.clone()), 1024, 2))
+            .collect();
+        for (n, single) in singles.iter().enumerate() {
+            assert_eq!(fanout.output_delay(n), single.output_delay());
+        }
+        for chunk in 0..5 {
+            let wave: Vec<f64> = (0..1024)
+                .map(|n| (0.01 * (n + 1024 * chunk) as f64).sin())
+                .collect();
+            let waves = vec![wave.clone(), wave];
+            let outputs = fanout.process(&waves).unwrap();
+            assert_eq!(outputs.len(), 3);
+            for (output, single) in outputs.iter().zip(singles.iter_mut()) {
+                let expected = single.process(&waves).unwrap();
+                for (out_chan, exp_chan) in output.iter().zip(expected.iter()) {
+                    assert_eq!(out_chan.len(), exp_chan.len());
+                    for (out_val, exp_val) in out_chan.iter().zip(exp_chan.iter()) {
+                        assert!((out_val - exp_val).abs() < 1.0e-9);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_as_fixed_in_cubic() {
+        compare_with_fixed_in(InterpolationType::Cubic);
+    }
+
+    #[test]
+    fn same_as_fixed_in_linear() {
+        compare_with_fixed_in(InterpolationType::Linear);
+    }
+
+    #[test]
+    fn same_as_fixed_in_nearest() {
+        compare_with_fixed_in(InterpolationType::Nearest);
+    }
+
+    #[test]
+    fn shared_tables() {
+        let fanout = SincFanOut::<f32>::new(
+            &[1.5, 2.0, 0.5, 0.5, 1.0],
+            params(InterpolationType::Cubic),
+            512,
+            1,
+        );
+        assert_eq!(fanout.interpolators.len(), 2);
+    }
+
+    #[test]
+    fn skipped_channel_and_wrong_frames() {
+        let mut fanout =
+            SincFanOut::<f64>::new(&[1.5, 0.5], params(InterpolationType::Linear), 512, 2);
+        let outputs = fanout.process(&[vec![0.0; 512], Vec::new()]).unwrap();
+        for output in outputs.iter() {
+            assert!(!output[0].is_empty());
+            assert!(output[1].is_empty());
+        }
+        assert!(fanout.process(&[vec![0.0; 512], vec![0.0; 511]]).is_err());
+        assert!(fanout.process(&[vec![0.0; 512]]).is_err());
+    }
+}
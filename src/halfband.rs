@@ -0,0 +1,548 @@
+use crate::error::{ResampleError, ResampleResult};
+#[cfg(all(target_arch = "x86_64", feature = "avx"))]
+use crate::halfband_avx::{AvxHalfband, HalfbandAvxSample};
+#[cfg(target_arch = "x86_64")]
+use crate::halfband_sse::{HalfbandSseSample, SseHalfband};
+use crate::sinc::sinc;
+use crate::windows::{make_window, WindowFunction};
+use crate::{Resampler, Sample};
+#[cfg(target_arch = "x86_64")]
+use std::any::Any;
+
+/// Functions for calculating the symmetric part of a halfband filter.
+pub trait HalfbandKernel<T> {
+    /// Filter `wave` with the nonzero coefficients on one side of the center tap,
+    /// using the symmetry to only make one multiplication per pair of taps.
+    /// With `m` coefficients this calculates
+    /// `wave_out[n] = sum_k coeffs[k] * (wave[n + m - 1 - k] + wave[n + m + k])`.
+    /// The input must be at least `wave_out.len() + 2 * m - 1` samples long.
+    fn symmetric_fir(&self, wave: &[T], wave_out: &mut [T]);
+
+    /// Get the number of coefficients
+    fn nbr_coeffs(&self) -> usize;
+}
+
+/// A plain scalar halfband filter kernel with no SIMD architecture specific optimizations.
+pub struct ScalarHalfband<T> {
+    coeffs: Vec<T>,
+}
+
+impl<T> HalfbandKernel<T> for ScalarHalfband<T>
+where
+    T: Sample,
+{
+    fn symmetric_fir(&self, wave: &[T], wave_out: &mut [T]) {
+        assert!(
+            wave.len() + 1 >= wave_out.len() + 2 * self.coeffs.len(),
+            "Input of {} samples is too short for {} output samples",
+            wave.len(),
+            wave_out.len()
+        );
+        symmetric_fir_scalar(wave, &self.coeffs, wave_out);
+    }
+
+    fn nbr_coeffs(&self) -> usize {
+        self.coeffs.len()
+    }
+}
+
+impl<T> ScalarHalfband<T>
+where
+    T: Sample,
+{
+    /// Create a new ScalarHalfband
+    ///
+    /// Parameters are:
+    /// - `coeffs`: The nonzero coefficients on one side of the center tap, see `make_halfband`.
+    pub fn new(coeffs: Vec<T>) -> Self {
+        Self { coeffs }
+    }
+}
+
+/// Helper function. Scalar implementation of the symmetric filter,
+/// also used by the SIMD kernels for the samples that don't fill a full vector.
+pub(crate) fn symmetric_fir_scalar<T>(wave: &[T], coeffs: &[T], wave_out: &mut [T])
+where
+    T: Sample,
+{
+    let m = coeffs.len();
+    for (n, out) in wave_out.iter_mut().enumerate() {
+        let mut acc = T::zero();
+        for (k, coeff) in coeffs.iter().enumerate() {
+            acc += *coeff * (wave[n + m - 1 - k] + wave[n + m + k]);
+        }
+        *out = acc;
+    }
+}
+
+/// The sample types that have SIMD halfband kernels, that is f32 and f64.
+#[cfg(all(target_arch = "x86_64", feature = "avx"))]
+trait SimdHalfbandSample: Sample + HalfbandAvxSample + HalfbandSseSample {}
+
+/// The sample types that have SIMD halfband kernels, that is f32 and f64.
+#[cfg(all(target_arch = "x86_64", not(feature = "avx")))]
+trait SimdHalfbandSample: Sample + HalfbandSseSample {}
+
+#[cfg(target_arch = "x86_64")]
+impl SimdHalfbandSample for f32 {}
+
+#[cfg(target_arch = "x86_64")]
+impl SimdHalfbandSample for f64 {}
+
+/// Make the fastest available SIMD kernel for the given coefficients, if any.
+#[cfg(target_arch = "x86_64")]
+fn make_simd_kernel<T>(coeffs: &[T]) -> Option<Box<dyn Any>>
+where
+    T: SimdHalfbandSample,
+{
+    #[cfg(feature = "avx")]
+    if let Ok(kernel) = AvxHalfband::<T>::new(coeffs.to_vec()) {
+        return Some(Box::new(Box::new(kernel) as Box<dyn HalfbandKernel<T>>));
+    }
+
+    if let Ok(kernel) = SseHalfband::<T>::new(coeffs.to_vec()) {
+        return Some(Box::new(Box::new(kernel) as Box<dyn HalfbandKernel<T>>));
+    }
+    None
+}
+
+/// Make the fastest available halfband kernel for the given coefficients.
+///
+/// The SIMD kernels only exist for f32 and f64, so they are picked by the type of the samples.
+/// For other sample types the scalar kernel is used.
+pub fn make_halfband_kernel<T>(coeffs: Vec<T>) -> Box<dyn HalfbandKernel<T>>
+where
+    T: Sample,
+{
+    #[cfg(target_arch = "x86_64")]
+    {
+        let any_coeffs: &dyn Any = &coeffs;
+        let kernel = if let Some(coeffs) = any_coeffs.downcast_ref::<Vec<f32>>() {
+            make_simd_kernel(coeffs)
+        } else if let Some(coeffs) = any_coeffs.downcast_ref::<Vec<f64>>() {
+            make_simd_kernel(coeffs)
+        } else {
+            None
+        };
+        // `T` is f32 or f64 when there is a kernel, so it is a `Box<dyn HalfbandKernel<T>>`.
+        if let Some(kernel) =
+            kernel.and_then(|kernel| kernel.downcast::<Box<dyn HalfbandKernel<T>>>().ok())
+        {
+            return *kernel;
+        }
+    }
+
+    Box::new(ScalarHalfband::<T>::new(coeffs))
+}
+
+/// Helper function. Make the coefficients of a windowed sinc halfband lowpass filter.
+///
+/// Every second coefficient of a halfband filter is zero, except the center one which is 0.5.
+/// Only the nonzero coefficients at offsets 1, 3, 5... from the center are returned,
+/// the filter is symmetric and the coefficients at negative offsets are the same.
+/// They are normalized to give a gain of exactly one at DC.
+/// The full filter is `4 * nbr_coeffs - 1` taps long.
+pub fn make_halfband<T>(nbr_coeffs: usize, windowfunc: WindowFunction) -> Vec<T>
+where
+    T: Sample,
+{
+    let center = 2 * nbr_coeffs;
+    let window = make_window::<T>(2 * center, windowfunc);
+    let mut coeffs: Vec<T> = (0..nbr_coeffs)
+        .map(|n| {
+            let offset = 2 * n + 1;
+            window[center + offset] * sinc(T::coerce(offset) / T::coerce(2))
+        })
+        .collect();
+    let sum = coeffs.iter().fold(T::zero(), |acc, c| acc + *c);
+    let scale = T::coerce(0.25) / sum;
+    coeffs.iter_mut().for_each(|c| *c *= scale);
+    debug!(
+        "Generate halfband filter, nbr coeffs: {}, normalized by: {:?}",
+        nbr_coeffs, sum
+    );
+    coeffs
+}
+
+/// A synchronous resampler that decimates by a factor of two using a halfband lowpass filter.
+/// It accepts a fixed number of audio frames for input and returns half as many frames.
+///
+/// Every second tap of a halfband filter is zero, and the filter is symmetric.
+/// The input is split into even and odd samples, where the even samples only need the
+/// center tap, and the odd ones are filtered with the nonzero taps, one multiplication per pair.
+pub struct HalfbandDecimator<T> {
+    nbr_channels: usize,
+    chunk_size: usize,
+    kernel: Box<dyn HalfbandKernel<T>>,
+    evens: Vec<Vec<T>>,
+    odds: Vec<Vec<T>>,
+}
+
+/// A synchronous resampler that interpolates by a factor of two using a halfband lowpass filter.
+/// It accepts a fixed number of audio frames for input and returns twice as many frames.
+///
+/// Every second output sample only depends on the center tap of the filter, and is a copy
+/// of an input sample. The others are calculated with the nonzero taps, one multiplication per pair.
+pub struct HalfbandInterpolator<T> {
+    nbr_channels: usize,
+    chunk_size: usize,
+    kernel: Box<dyn HalfbandKernel<T>>,
+    buffers: Vec<Vec<T>>,
+    scratch: Vec<T>,
+}
+
+impl<T> HalfbandDecimator<T>
+where
+    T: Sample,
+{
+    /// Create a new HalfbandDecimator
+    ///
+    /// Parameters are:
+    /// - `nbr_coeffs`: number of nonzero coefficients on each side of the center tap.
+    /// - `window`: window function used for the filter.
+    /// - `chunk_size`: desired size of input data in frames, rounded up to an even number.
+    /// - `nbr_channels`: number of channels in input/output.
+    pub fn new(
+        nbr_coeffs: usize,
+        window: WindowFunction,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new HalfbandDecimator, nbr_coeffs: {}, chunk_size: {}, channels: {}",
+            nbr_coeffs, chunk_size, nbr_channels
+        );
+        let kernel = make_halfband_kernel(make_halfband(nbr_coeffs, window));
+        HalfbandDecimator {
+            nbr_channels,
+            chunk_size: chunk_size + chunk_size % 2,
+            kernel,
+            evens: vec![vec![T::zero(); 2 * nbr_coeffs]; nbr_channels],
+            odds: vec![vec![T::zero(); 2 * nbr_coeffs]; nbr_channels],
+        }
+    }
+
+    /// Decimate a single channel. This does not check the chunk size of the instance,
+    /// and can be used to process blocks of any even length without allocating.
+    /// The output must be half as long as the input.
+    pub fn process_channel(&mut self, chan: usize, wave_in: &[T], wave_out: &mut [T]) {
+        assert!(
+            wave_in.len() == 2 * wave_out.len(),
+            "Output length {} must be half of input length {}",
+            wave_out.len(),
+            wave_in.len()
+        );
+        let nbr_coeffs = self.kernel.nbr_coeffs();
+        let history = 2 * nbr_coeffs;
+        let evens = &mut self.evens[chan];
+        let odds = &mut self.odds[chan];
+        evens.truncate(history);
+        odds.truncate(history);
+        evens.extend(wave_in.iter().step_by(2));
+        odds.extend(wave_in.iter().skip(1).step_by(2));
+        self.kernel.symmetric_fir(odds, wave_out);
+        let half = T::coerce(0.5);
+        for (out, even) in wave_out.iter_mut().zip(evens.iter().skip(nbr_coeffs)) {
+            *out += half * *even;
+        }
+        let len = evens.len();
+        evens.copy_within(len - history.., 0);
+        odds.copy_within(len - history.., 0);
+    }
+}
+
+impl<T> HalfbandInterpolator<T>
+where
+    T: Sample,
+{
+    /// Create a new HalfbandInterpolator
+    ///
+    /// Parameters are:
+    /// - `nbr_coeffs`: number of nonzero coefficients on each side of the center tap.
+    /// - `window`: window function used for the filter.
+    /// - `chunk_size`: size of input data in frames.
+    /// - `nbr_channels`: number of channels in input/output.
+    pub fn new(
+        nbr_coeffs: usize,
+        window: WindowFunction,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new HalfbandInterpolator, nbr_coeffs: {}, chunk_size: {}, channels: {}",
+            nbr_coeffs, chunk_size, nbr_channels
+        );
+        // the zero stuffed signal needs a gain of two
+        let coeffs = make_halfband::<T>(nbr_coeffs, window)
+            .iter()
+            .map(|c| T::coerce(2.0) * *c)
+            .collect();
+        HalfbandInterpolator {
+            nbr_channels,
+            chunk_size,
+            kernel: make_halfband_kernel(coeffs),
+            buffers: vec![vec![T::zero(); 2 * nbr_coeffs]; nbr_channels],
+            scratch: vec![T::zero(); chunk_size],
+        }
+    }
+
+    /// Interpolate a single channel. This does not check the chunk size of the instance,
+    /// and can be used to process blocks of any length. The output must be twice as long
+    /// as the input. Allocates only when given a longer block than before.
+    pub fn process_channel(&mut self, chan: usize, wave_in: &[T], wave_out: &mut [T]) {
+        assert!(
+            2 * wave_in.len() == wave_out.len(),
+            "Output length {} must be twice the input length {}",
+            wave_out.len(),
+            wave_in.len()
+        );
+        let nbr_coeffs = self.kernel.nbr_coeffs();
+        let history = 2 * nbr_coeffs;
+        let buf = &mut self.buffers[chan];
+        buf.truncate(history);
+        buf.extend_from_slice(wave_in);
+        if self.scratch.len() < wave_in.len() {
+            self.scratch.resize(wave_in.len(), T::zero());
+        }
+        let odds = &mut self.scratch[..wave_in.len()];
+        self.kernel.symmetric_fir(&buf[1..], odds);
+        for ((out, even), odd) in wave_out
+            .chunks_exact_mut(2)
+            .zip(buf.iter().skip(nbr_coeffs))
+            .zip(odds.iter())
+        {
+            out[0] = *even;
+            out[1] = *odd;
+        }
+        let len = buf.len();
+        buf.copy_within(len - history.., 0);
+    }
+}
+
+/// Check the channels and frames of an input chunk, and return the channels to process.
+fn check_input<T, V: AsRef<[T]>>(
+    wave_in: &[V],
+    nbr_channels: usize,
+    chunk_size: usize,
+) -> ResampleResult<Vec<usize>> {
+    if wave_in.len() != nbr_channels {
+        return Err(ResampleError::WrongNumberOfChannels {
+            expected: nbr_channels,
+            actual: wave_in.len(),
+        });
+    }
+    let mut used_channels = Vec::new();
+    for (chan, wave) in wave_in.iter().enumerate() {
+        let wave = wave.as_ref();
+        if !wave.is_empty() {
+            used_channels.push(chan);
+            if wave.len() != chunk_size {
+                return Err(ResampleError::WrongNumberOfFrames {
+                    channel: chan,
+                    expected: chunk_size,
+                    actual: wave.len(),
+                });
+            }
+        }
+    }
+    Ok(used_channels)
+}
+
+impl<T> Resampler<T> for HalfbandDecimator<T>
+where
+    T: Sample,
+{
+    /// Resample a chunk of audio. The output is half as long as the input.
+    /// If the waveform for a channel is empty, this channel will be ignored and produce a
+    /// corresponding empty output waveform.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input data is not equal
+    /// to the number of channels and chunk size of the instance.
+    fn process<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<Vec<Vec<T>>> {
+        let used_channels = check_input(wave_in, self.nbr_channels, self.chunk_size)?;
+        let mut wave_out = vec![Vec::new(); self.nbr_channels];
+        for chan in used_channels {
+            wave_out[chan] = vec![T::zero(); self.chunk_size / 2];
+            self.process_channel(chan, wave_in[chan].as_ref(), &mut wave_out[chan]);
+        }
+        Ok(wave_out)
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    /// Will always return the chunk_size of the instance.
+    fn nbr_frames_needed(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Get the number of frames returned by "process", half the chunk size.
+    fn output_frames_max(&self) -> usize {
+        self.chunk_size / 2
+    }
+
+    /// Update the resample ratio. This is not supported by this resampler and
+    /// always returns an error.
+    fn set_resample_ratio(&mut self, _new_ratio: f64) -> ResampleResult<()> {
+        Err(ResampleError::SyncNotAdjustable)
+    }
+
+    /// Update the resample ratio relative to the original one. This is not
+    /// supported by this resampler and always returns an error.
+    fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> ResampleResult<()> {
+        Err(ResampleError::SyncNotAdjustable)
+    }
+
+    /// Get the delay of the output relative to the input, in output frames.
+    fn output_delay(&self) -> usize {
+        self.kernel.nbr_coeffs()
+    }
+}
+
+impl<T> Resampler<T> for HalfbandInterpolator<T>
+where
+    T: Sample,
+{
+    /// Resample a chunk of audio. The output is twice as long as the input.
+    /// If the waveform for a channel is empty, this channel will be ignored and produce a
+    /// corresponding empty output waveform.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input data is not equal
+    /// to the number of channels and chunk size of the instance.
+    fn process<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<Vec<Vec<T>>> {
+        let used_channels = check_input(wave_in, self.nbr_channels, self.chunk_size)?;
+        let mut wave_out = vec![Vec::new(); self.nbr_channels];
+        for chan in used_channels {
+            wave_out[chan] = vec![T::zero(); 2 * self.chunk_size];
+            self.process_channel(chan, wave_in[chan].as_ref(), &mut wave_out[chan]);
+        }
+        Ok(wave_out)
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    /// Will always return the chunk_size of the instance.
+    fn nbr_frames_needed(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Get the number of frames returned by "process", twice the chunk size.
+    fn output_frames_max(&self) -> usize {
+        2 * self.chunk_size
+    }
+
+    /// Update the resample ratio. This is not supported by this resampler and
+    /// always returns an error.
+    fn set_resample_ratio(&mut self, _new_ratio: f64) -> ResampleResult<()> {
+        Err(ResampleError::SyncNotAdjustable)
+    }
+
+    /// Update the resample ratio relative to the original one. This is not
+    /// supported by this resampler and always returns an error.
+    fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> ResampleResult<()> {
+        Err(ResampleError::SyncNotAdjustable)
+    }
+
+    /// Get the delay of the output relative to the input, in output frames.
+    fn output_delay(&self) -> usize {
+        2 * self.kernel.nbr_coeffs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::halfband::{make_halfband, HalfbandKernel, ScalarHalfband};
+    use crate::{HalfbandDecimator, HalfbandInterpolator, Resampler, WindowFunction};
+
+    fn amplitude(wave: &[f64]) -> f64 {
+        wave.iter().cloned().fold(0.0, |acc, x| acc.max(x.abs()))
+    }
+
+    #[test]
+    fn halfband_coeffs() {
+        let coeffs = make_halfband::<f64>(12, WindowFunction::BlackmanHarris2);
+        assert_eq!(coeffs.len(), 12);
+        let sum = 0.5 + 2.0 * coeffs.iter().sum::<f64>();
+        assert!((sum - 1.0).abs() < 1.0e-12);
+        assert!(coeffs[0] > 0.3);
+        assert!(coeffs[1] < 0.0);
+    }
+
+    #[test]
+    fn scalar_kernel() {
+        let kernel = ScalarHalfband::<f64>::new(vec![1.0, 10.0]);
+        let wave = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        let mut out = vec![0.0; 2];
+        kernel.symmetric_fir(&wave, &mut out);
+        assert_eq!(out, vec![1.0 * (2.0 + 3.0) + 10.0 * (1.0 + 4.0), 77.0]);
+    }
+
+    #[test]
+    fn decimate_sines() {
+        let mut decimator =
+            HalfbandDecimator::<f64>::new(12, WindowFunction::BlackmanHarris2, 2048, 1);
+        let pass: Vec<f64> = (0..2048).map(|n| (0.2 * n as f64).sin()).collect();
+        let out = decimator.process(&[pass]).unwrap();
+        assert!((amplitude(&out[0][100..]) - 1.0).abs() < 1.0e-3);
+
+        let mut decimator =
+            HalfbandDecimator::<f64>::new(12, WindowFunction::BlackmanHarris2, 2048, 1);
+        let stop: Vec<f64> = (0..2048).map(|n| (2.6 * n as f64).sin()).collect();
+        let out = decimator.process(&[stop]).unwrap();
+        assert!(amplitude(&out[0][100..]) < 1.0e-6);
+    }
+
+    #[test]
+    fn decimate_delay() {
+        let mut decimator =
+            HalfbandDecimator::<f64>::new(8, WindowFunction::BlackmanHarris2, 64, 1);
+        let mut wave = vec![0.0; 64];
+        wave[0] = 1.0;
+        let out = decimator.process(&[wave]).unwrap();
+        assert_eq!(decimator.output_delay(), 8);
+        assert!((out[0][8] - 0.5).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn decimate_odd_chunk_size() {
+        let decimator = HalfbandDecimator::<f32>::new(8, WindowFunction::BlackmanHarris2, 1023, 1);
+        assert_eq!(decimator.nbr_frames_needed(), 1024);
+    }
+
+    #[test]
+    fn decimate_skipped() {
+        let mut decimator =
+            HalfbandDecimator::<f32>::new(8, WindowFunction::BlackmanHarris2, 256, 2);
+        let waves = vec![Vec::new(), vec![0.0f32; 256]];
+        let out = decimator.process(&waves).unwrap();
+        assert!(out[0].is_empty());
+        assert_eq!(out[1].len(), 128);
+    }
+
+    #[test]
+    fn interpolate_sine_in_chunks() {
+        let mut interpolator =
+            HalfbandInterpolator::<f64>::new(12, WindowFunction::BlackmanHarris2, 128, 2);
+        let wave: Vec<f64> = (0..1024).map(|n| (0.4 * n as f64).sin()).collect();
+        let mut out = Vec::new();
+        for chunk in wave.chunks(128) {
+            let waves = vec![Vec::new(), chunk.to_vec()];
+            let result = interpolator.process(&waves).unwrap();
+            assert!(result[0].is_empty());
+            out.extend_from_slice(&result[1]);
+        }
+        let delay = interpolator.output_delay();
+        assert_eq!(delay, 24);
+        for (n, val) in out.iter().enumerate().skip(200) {
+            let expected = (0.2 * (n - delay) as f64).sin();
+            assert!((val - expected).abs() < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn interpolate_wrong_frames() {
+        let mut interpolator =
+            HalfbandInterpolator::<f64>::new(12, WindowFunction::BlackmanHarris2, 128, 1);
+        let waves = vec![vec![0.0f64; 100]];
+        assert!(interpolator.process(&waves).is_err());
+    }
+}
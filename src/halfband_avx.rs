@@ -0,0 +1,182 @@
+use crate::error::{CpuFeature, MissingCpuFeature};
+use crate::halfband::{symmetric_fir_scalar, HalfbandKernel};
+use crate::Sample;
+use core::arch::x86_64::{__m256, __m256d};
+use core::arch::x86_64::{
+    _mm256_add_pd, _mm256_fmadd_pd, _mm256_loadu_pd, _mm256_set1_pd, _mm256_setzero_pd, _mm256_storeu_pd,
+};
+use core::arch::x86_64::{
+    _mm256_add_ps, _mm256_fmadd_ps, _mm256_loadu_ps, _mm256_set1_ps, _mm256_setzero_ps, _mm256_storeu_ps,
+};
+
+/// Collection of cpu features required for this kernel.
+static FEATURES: &[CpuFeature] = &[CpuFeature::Avx, CpuFeature::Fma];
+
+/// Trait governing what can be done with an HalfbandAvxSample.
+pub trait HalfbandAvxSample: Sized {
+    type Coeff;
+
+    /// Pack coefficients into vectors, with the same value in all lanes.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because it uses target_enable dispatching. There are no
+    /// special requirements from the caller.
+    unsafe fn pack_coeffs(coeffs: &[Self]) -> Vec<Self::Coeff>;
+
+    /// Calculate the symmetric filter for as many output samples as fill complete vectors.
+    /// Returns the number of output samples that were calculated.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that `wave` is at least `wave_out.len() + 2 * coeffs.len() - 1`
+    /// samples long.
+    unsafe fn symmetric_fir_unsafe(
+        wave: &[Self],
+        wave_out: &mut [Self],
+        coeffs: &[Self::Coeff],
+    ) -> usize;
+}
+
+impl HalfbandAvxSample for f32 {
+    type Coeff = __m256;
+
+    #[target_feature(enable = "avx", enable = "fma")]
+    unsafe fn pack_coeffs(coeffs: &[f32]) -> Vec<Self::Coeff> {
+        coeffs.iter().map(|c| _mm256_set1_ps(*c)).collect()
+    }
+
+    #[target_feature(enable = "avx", enable = "fma")]
+    unsafe fn symmetric_fir_unsafe(
+        wave: &[f32],
+        wave_out: &mut [f32],
+        coeffs: &[Self::Coeff],
+    ) -> usize {
+        let m = coeffs.len();
+        let nbr_full = wave_out.len() / 8 * 8;
+        for n in (0..nbr_full).step_by(8) {
+            let mut acc = _mm256_setzero_ps();
+            for (k, coeff) in coeffs.iter().enumerate() {
+                let lo = _mm256_loadu_ps(wave.get_unchecked(n + m - 1 - k));
+                let hi = _mm256_loadu_ps(wave.get_unchecked(n + m + k));
+                acc = _mm256_fmadd_ps(_mm256_add_ps(lo, hi), *coeff, acc);
+            }
+            _mm256_storeu_ps(wave_out.get_unchecked_mut(n), acc);
+        }
+        nbr_full
+    }
+}
+
+impl HalfbandAvxSample for f64 {
+    type Coeff = __m256d;
+
+    #[target_feature(enable = "avx", enable = "fma")]
+    unsafe fn pack_coeffs(coeffs: &[f64]) -> Vec<Self::Coeff> {
+        coeffs.iter().map(|c| _mm256_set1_pd(*c)).collect()
+    }
+
+    #[target_feature(enable = "avx", enable = "fma")]
+    unsafe fn symmetric_fir_unsafe(
+        wave: &[f64],
+        wave_out: &mut [f64],
+        coeffs: &[Self::Coeff],
+    ) -> usize {
+        let m = coeffs.len();
+        let nbr_full = wave_out.len() / 8 * 8;
+        for n in (0..nbr_full).step_by(8) {
+            let mut acc0 = _mm256_setzero_pd();
+            let mut acc1 = _mm256_setzero_pd();
+            for (k, coeff) in coeffs.iter().enumerate() {
+                let lo0 = _mm256_loadu_pd(wave.get_unchecked(n + m - 1 - k));
+                let hi0 = _mm256_loadu_pd(wave.get_unchecked(n + m + k));
+                let lo1 = _mm256_loadu_pd(wave.get_unchecked(n + m + 3 - k));
+                let hi1 = _mm256_loadu_pd(wave.get_unchecked(n + m + k + 4));
+                acc0 = _mm256_fmadd_pd(_mm256_add_pd(lo0, hi0), *coeff, acc0);
+                acc1 = _mm256_fmadd_pd(_mm256_add_pd(lo1, hi1), *coeff, acc1);
+            }
+            _mm256_storeu_pd(wave_out.get_unchecked_mut(n), acc0);
+            _mm256_storeu_pd(wave_out.get_unchecked_mut(n + 4), acc1);
+        }
+        nbr_full
+    }
+}
+
+/// An AVX accelerated halfband filter kernel
+pub struct AvxHalfband<T> where T: HalfbandAvxSample {
+    coeffs: Vec<T>,
+    packed: Vec<T::Coeff>,
+}
+
+impl<T> HalfbandKernel<T> for AvxHalfband<T> where T: Sample + HalfbandAvxSample {
+    fn symmetric_fir(&self, wave: &[T], wave_out: &mut [T]) {
+        assert!(wave.len() + 1 >= wave_out.len() + 2 * self.coeffs.len(), "Input of {} samples is too short for {} output samples", wave.len(), wave_out.len());
+        let done = unsafe { <T as HalfbandAvxSample>::symmetric_fir_unsafe(wave, wave_out, &self.packed) };
+        symmetric_fir_scalar(&wave[done..], &self.coeffs, &mut wave_out[done..]);
+    }
+
+    fn nbr_coeffs(&self) -> usize {
+        self.coeffs.len()
+    }
+}
+
+impl<T> AvxHalfband<T> where T: Sample + HalfbandAvxSample {
+    /// Create a new AvxHalfband
+    ///
+    /// Parameters are:
+    /// - `coeffs`: The nonzero coefficients on one side of the center tap.
+    pub fn new(coeffs: Vec<T>) -> Result<Self, MissingCpuFeature> {
+        if let Some(feature) = FEATURES.iter().find(|f| !f.is_detected()) {
+            return Err(MissingCpuFeature(*feature));
+        }
+
+        let packed = unsafe { <T as HalfbandAvxSample>::pack_coeffs(&coeffs) };
+        Ok(Self { coeffs, packed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::halfband::{make_halfband, HalfbandKernel, ScalarHalfband};
+    use crate::halfband_avx::AvxHalfband;
+    use crate::WindowFunction;
+    use rand::Rng;
+
+    #[test]
+    fn test_avx_halfband_64() {
+        let mut rng = rand::thread_rng();
+        let mut wave = Vec::new();
+        for _ in 0..2048 {
+            wave.push(rng.gen::<f64>());
+        }
+        let coeffs = make_halfband::<f64>(24, WindowFunction::BlackmanHarris2);
+        let kernel = AvxHalfband::<f64>::new(coeffs.clone()).unwrap();
+        let scalar = ScalarHalfband::<f64>::new(coeffs);
+        // odd length to also check the samples that don't fill a vector
+        let mut value = vec![0.0; 1003];
+        let mut check = vec![0.0; 1003];
+        kernel.symmetric_fir(&wave[7..], &mut value);
+        scalar.symmetric_fir(&wave[7..], &mut check);
+        for (v, c) in value.iter().zip(check.iter()) {
+            assert!((v - c).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn test_avx_halfband_32() {
+        let mut rng = rand::thread_rng();
+        let mut wave = Vec::new();
+        for _ in 0..2048 {
+            wave.push(rng.gen::<f32>());
+        }
+        let coeffs = make_halfband::<f32>(24, WindowFunction::BlackmanHarris2);
+        let kernel = AvxHalfband::<f32>::new(coeffs.clone()).unwrap();
+        let scalar = ScalarHalfband::<f32>::new(coeffs);
+        let mut value = vec![0.0; 1003];
+        let mut check = vec![0.0; 1003];
+        kernel.symmetric_fir(&wave[7..], &mut value);
+        scalar.symmetric_fir(&wave[7..], &mut check);
+        for (v, c) in value.iter().zip(check.iter()) {
+            assert!((v - c).abs() < 1.0e-5);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::error::{CpuFeature, MissingCpuFeature};
+use crate::halfband::{symmetric_fir_scalar, HalfbandKernel};
+use crate::Sample;
+use core::arch::x86_64::{__m128, __m128d};
+use core::arch::x86_64::{_mm_add_pd, _mm_loadu_pd, _mm_mul_pd, _mm_set1_pd, _mm_setzero_pd, _mm_storeu_pd};
+use core::arch::x86_64::{_mm_add_ps, _mm_loadu_ps, _mm_mul_ps, _mm_set1_ps, _mm_setzero_ps, _mm_storeu_ps};
+
+/// Collection of cpu features required for this kernel.
+static FEATURES: &[CpuFeature] = &[CpuFeature::Sse3];
+
+/// Trait governing what can be done with an HalfbandSseSample.
+pub trait HalfbandSseSample: Sized {
+    type Coeff;
+
+    /// Pack coefficients into vectors, with the same value in all lanes.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because it uses target_enable dispatching. There are no
+    /// special requirements from the caller.
+    unsafe fn pack_coeffs(coeffs: &[Self]) -> Vec<Self::Coeff>;
+
+    /// Calculate the symmetric filter for as many output samples as fill complete vectors.
+    /// Returns the number of output samples that were calculated.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that `wave` is at least `wave_out.len() + 2 * coeffs.len() - 1`
+    /// samples long.
+    unsafe fn symmetric_fir_unsafe(
+        wave: &[Self],
+        wave_out: &mut [Self],
+        coeffs: &[Self::Coeff],
+    ) -> usize;
+}
+
+impl HalfbandSseSample for f32 {
+    type Coeff = __m128;
+
+    #[target_feature(enable = "sse3")]
+    unsafe fn pack_coeffs(coeffs: &[f32]) -> Vec<Self::Coeff> {
+        coeffs.iter().map(|c| _mm_set1_ps(*c)).collect()
+    }
+
+    #[target_feature(enable = "sse3")]
+    unsafe fn symmetric_fir_unsafe(
+        wave: &[f32],
+        wave_out: &mut [f32],
+        coeffs: &[Self::Coeff],
+    ) -> usize {
+        let m = coeffs.len();
+        let nbr_full = wave_out.len() / 4 * 4;
+        for n in (0..nbr_full).step_by(4) {
+            let mut acc = _mm_setzero_ps();
+            for (k, coeff) in coeffs.iter().enumerate() {
+                let lo = _mm_loadu_ps(wave.get_unchecked(n + m - 1 - k));
+                let hi = _mm_loadu_ps(wave.get_unchecked(n + m + k));
+                acc = _mm_add_ps(acc, _mm_mul_ps(_mm_add_ps(lo, hi), *coeff));
+            }
+            _mm_storeu_ps(wave_out.get_unchecked_mut(n), acc);
+        }
+        nbr_full
+    }
+}
+
+impl HalfbandSseSample for f64 {
+    type Coeff = __m128d;
+
+    #[target_feature(enable = "sse3")]
+    unsafe fn pack_coeffs(coeffs: &[f64]) -> Vec<Self::Coeff> {
+        coeffs.iter().map(|c| _mm_set1_pd(*c)).collect()
+    }
+
+    #[target_feature(enable = "sse3")]
+    unsafe fn symmetric_fir_unsafe(
+        wave: &[f64],
+        wave_out: &mut [f64],
+        coeffs: &[Self::Coeff],
+    ) -> usize {
+        let m = coeffs.len();
+        let nbr_full = wave_out.len() / 4 * 4;
+        for n in (0..nbr_full).step_by(4) {
+            let mut acc0 = _mm_setzero_pd();
+            let mut acc1 = _mm_setzero_pd();
+            for (k, coeff) in coeffs.iter().enumerate() {
+                let lo0 = _mm_loadu_pd(wave.get_unchecked(n + m - 1 - k));
+                let hi0 = _mm_loadu_pd(wave.get_unchecked(n + m + k));
+                let lo1 = _mm_loadu_pd(wave.get_unchecked(n + m + 1 - k));
+                let hi1 = _mm_loadu_pd(wave.get_unchecked(n + m + k + 2));
+                acc0 = _mm_add_pd(acc0, _mm_mul_pd(_mm_add_pd(lo0, hi0), *coeff));
+                acc1 = _mm_add_pd(acc1, _mm_mul_pd(_mm_add_pd(lo1, hi1), *coeff));
+            }
+            _mm_storeu_pd(wave_out.get_unchecked_mut(n), acc0);
+            _mm_storeu_pd(wave_out.get_unchecked_mut(n + 2), acc1);
+        }
+        nbr_full
+    }
+}
+
+/// A SSE accelerated halfband filter kernel
+pub struct SseHalfband<T> where T: HalfbandSseSample {
+    coeffs: Vec<T>,
+    packed: Vec<T::Coeff>,
+}
+
+impl<T> HalfbandKernel<T> for SseHalfband<T> where T: Sample + HalfbandSseSample {
+    fn symmetric_fir(&self, wave: &[T], wave_out: &mut [T]) {
+        assert!(wave.len() + 1 >= wave_out.len() + 2 * self.coeffs.len(), "Input of {} samples is too short for {} output samples", wave.len(), wave_out.len());
+        let done = unsafe { <T as HalfbandSseSample>::symmetric_fir_unsafe(wave, wave_out, &self.packed) };
+        symmetric_fir_scalar(&wave[done..], &self.coeffs, &mut wave_out[done..]);
+    }
+
+    fn nbr_coeffs(&self) -> usize {
+        self.coeffs.len()
+    }
+}
+
+impl<T> SseHalfband<T> where T: Sample + HalfbandSseSample {
+    /// Create a new SseHalfband
+    ///
+    /// Parameters are:
+    /// - `coeffs`: The nonzero coefficients on one side of the center tap.
+    pub fn new(coeffs: Vec<T>) -> Result<Self, MissingCpuFeature> {
+        if let Some(feature) = FEATURES.iter().find(|f| !f.is_detected()) {
+            return Err(MissingCpuFeature(*feature));
+        }
+
+        let packed = unsafe { <T as HalfbandSseSample>::pack_coeffs(&coeffs) };
+        Ok(Self { coeffs, packed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::halfband::{make_halfband, HalfbandKernel, ScalarHalfband};
+    use crate::halfband_sse::SseHalfband;
+    use crate::WindowFunction;
+    use rand::Rng;
+
+    #[test]
+    fn test_sse_halfband_64() {
+        let mut rng = rand::thread_rng();
+        let mut wave = Vec::new();
+        for _ in 0..2048 {
+            wave.push(rng.gen::<f64>());
+        }
+        let coeffs = make_halfband::<f64>(24, WindowFunction::BlackmanHarris2);
+        let kernel = SseHalfband::<f64>::new(coeffs.clone()).unwrap();
+        let scalar = ScalarHalfband::<f64>::new(coeffs);
+        // odd length to also check the samples that don't fill a vector
+        let mut value = vec![0.0; 1003];
+        let mut check = vec![0.0; 1003];
+        kernel.symmetric_fir(&wave[7..], &mut value);
+        scalar.symmetric_fir(&wave[7..], &mut check);
+        for (v, c) in value.iter().zip(check.iter()) {
+            assert!((v - c).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn test_sse_halfband_32() {
+        let mut rng = rand::thread_rng();
+        let mut wave = Vec::new();
+        for _ in 0..2048 {
+            wave.push(rng.gen::<f32>());
+        }
+        let coeffs = make_halfband::<f32>(24, WindowFunction::BlackmanHarris2);
+        let kernel = SseHalfband::<f32>::new(coeffs.clone()).unwrap();
+        let scalar = ScalarHalfband::<f32>::new(coeffs);
+        let mut value = vec![0.0; 1003];
+        let mut check = vec![0.0; 1003];
+        kernel.symmetric_fir(&wave[7..], &mut value);
+        scalar.symmetric_fir(&wave[7..], &mut check);
+        for (v, c) in value.iter().zip(check.iter()) {
+            assert!((v - c).abs() < 1.0e-5);
+        }
+    }
+}
@@ -31,6 +31,26 @@ pub fn get_nearest_times_4(t: f64, factor: isize, points: &mut [(isize, isize);
     }
 }
 
+/// Get the six nearest time points for time t in format (index, subindex).
+pub fn get_nearest_times_6(t: f64, factor: isize, points: &mut [(isize, isize); 6]) {
+    let start = t.floor() as isize;
+    let frac = ((t - t.floor()) * (factor as f64)).floor() as isize;
+    let mut index;
+    let mut subindex;
+    for (idx, sub) in (-2..4).enumerate() {
+        index = start;
+        subindex = frac + sub;
+        if subindex < 0 {
+            subindex += factor;
+            index -= 1;
+        } else if subindex >= factor {
+            subindex -= factor;
+            index += 1;
+        }
+        points[idx] = (index, subindex);
+    }
+}
+
 /// Get the nearest time point for time t in format (index, subindex).
 pub fn get_nearest_time(t: f64, factor: isize) -> (isize, isize) {
     let mut index = t.floor() as isize;
@@ -47,6 +67,7 @@ mod tests {
     use crate::interpolation::get_nearest_time;
     use crate::interpolation::get_nearest_times_2;
     use crate::interpolation::get_nearest_times_4;
+    use crate::interpolation::get_nearest_times_6;
 
     #[test]
     fn get_nearest_2() {
@@ -90,6 +111,32 @@ mod tests {
         assert_eq!(times[3], (0, 1));
     }
 
+    #[test]
+    fn get_nearest_6() {
+        let t = 5.9f64;
+        let mut times = [(0isize, 0isize); 6];
+        get_nearest_times_6(t, 8, &mut times);
+        assert_eq!(times[0], (5, 5));
+        assert_eq!(times[1], (5, 6));
+        assert_eq!(times[2], (5, 7));
+        assert_eq!(times[3], (6, 0));
+        assert_eq!(times[4], (6, 1));
+        assert_eq!(times[5], (6, 2));
+    }
+
+    #[test]
+    fn get_nearest_6_zero() {
+        let t = -0.00001f64;
+        let mut times = [(0isize, 0isize); 6];
+        get_nearest_times_6(t, 8, &mut times);
+        assert_eq!(times[0], (-1, 5));
+        assert_eq!(times[1], (-1, 6));
+        assert_eq!(times[2], (-1, 7));
+        assert_eq!(times[3], (0, 0));
+        assert_eq!(times[4], (0, 1));
+        assert_eq!(times[5], (0, 2));
+    }
+
     #[test]
     fn get_nearest_single() {
         let t = 5.5f64;
@@ -11,15 +11,15 @@ use core::arch::x86_64::{
 use core::arch::x86_64::{
     _mm256_fmadd_ps, _mm256_loadu_ps, _mm256_setzero_ps, _mm_add_ps, _mm_hadd_ps, _mm_store_ss,
 };
-use crate::asynchro::SincInterpolator;
+use crate::asynchro::{check_multi, check_phases, cut_block, SincInterpolator, CHANNEL_BLOCK};
 use crate::Sample;
 
 /// Collection of cpu features required for this interpolator.
 static FEATURES: &[CpuFeature] = &[CpuFeature::Avx, CpuFeature::Fma];
 
 /// Trait governing what can be done with an AvxSample.
-pub trait AvxSample: Sized {
-    type Sinc;
+pub trait AvxSample: Sized + Copy {
+    type Sinc: Send + Sync;
 
     /// Pack sincs into a vector.
     /// 
@@ -42,6 +42,60 @@ pub trait AvxSample: Sized {
         sincs: &[Vec<Self::Sinc>],
         length: usize,
     ) -> Self;
+
+    /// Interpolate a sinc sample for a block of `CHANNEL_BLOCK` channels,
+    /// loading each part of the sinc only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `waves` contains `CHANNEL_BLOCK` channels.
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[Self]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; CHANNEL_BLOCK];
+
+    /// Interpolate a sinc sample for `N` subindices at the same index,
+    /// loading each part of the waveform only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `subindices` contains `N` values.
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[Self],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; N];
+}
+
+/// Sum the elements of an accumulator.
+#[target_feature(enable = "avx")]
+unsafe fn sum_ps(acc: __m256) -> f32 {
+    let acc_high = _mm256_extractf128_ps(acc, 1);
+    let acc_low = _mm_add_ps(acc_high, _mm256_castps256_ps128(acc));
+    let temp2 = _mm_hadd_ps(acc_low, acc_low);
+    let temp1 = _mm_hadd_ps(temp2, temp2);
+    let mut result = 0.0;
+    _mm_store_ss(&mut result, temp1);
+    result
+}
+
+/// Sum the elements of a pair of accumulators.
+#[target_feature(enable = "avx")]
+unsafe fn sum_pd(acc0: __m256d, acc1: __m256d) -> f64 {
+    let acc_all = _mm256_add_pd(acc0, acc1);
+    let acc_high = _mm256_extractf128_pd(acc_all, 1);
+    let temp2 = _mm_add_pd(acc_high, _mm256_castpd256_pd128(acc_all));
+    let temp1 = _mm_hadd_pd(temp2, temp2);
+    let mut result = 0.0;
+    _mm_store_sd(&mut result, temp1);
+    result
 }
 
 impl AvxSample for f32 {
@@ -78,12 +132,62 @@ impl AvxSample for f32 {
             acc = _mm256_fmadd_ps(w, *sinc.get_unchecked(s_idx), acc);
             w_idx += 8;
         }
-        let acc_high = _mm256_extractf128_ps(acc, 1);
-        let acc_low = _mm_add_ps(acc_high, _mm256_castps256_ps128(acc));
-        let temp2 = _mm_hadd_ps(acc_low, acc_low);
-        let temp1 = _mm_hadd_ps(temp2, temp2);
-        let mut result = 0.0;
-        _mm_store_ss(&mut result, temp1);
+        sum_ps(acc)
+    }
+
+    #[target_feature(enable = "avx", enable = "fma")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f32]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let mut accs = [_mm256_setzero_ps(); CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        for s_idx in 0..length / 8 {
+            let s = *sinc.get_unchecked(s_idx);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w = _mm256_loadu_ps(cut.get_unchecked(w_idx));
+                *acc = _mm256_fmadd_ps(w, s, *acc);
+            }
+            w_idx += 8;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_ps(*acc);
+        }
+        result
+    }
+
+    #[target_feature(enable = "avx", enable = "fma")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f32],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let mut accs = [_mm256_setzero_ps(); N];
+        let mut w_idx = 0;
+        for s_idx in 0..length / 8 {
+            let w = _mm256_loadu_ps(wave_cut.get_unchecked(w_idx));
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                *acc = _mm256_fmadd_ps(w, *sinc.get_unchecked(s_idx), *acc);
+            }
+            w_idx += 8;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_ps(*acc);
+        }
         result
     }
 }
@@ -127,12 +231,71 @@ impl AvxSample for f64 {
             w_idx += 8;
             s_idx += 2;
         }
-        let acc_all = _mm256_add_pd(acc0, acc1);
-        let acc_high = _mm256_extractf128_pd(acc_all, 1);
-        let temp2 = _mm_add_pd(acc_high, _mm256_castpd256_pd128(acc_all));
-        let temp1 = _mm_hadd_pd(temp2, temp2);
-        let mut result = 0.0;
-        _mm_store_sd(&mut result, temp1);
+        sum_pd(acc0, acc1)
+    }
+
+    #[target_feature(enable = "avx", enable = "fma")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f64]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let mut accs = [[_mm256_setzero_pd(); 2]; CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let s0 = *sinc.get_unchecked(s_idx);
+            let s1 = *sinc.get_unchecked(s_idx + 1);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w0 = _mm256_loadu_pd(cut.get_unchecked(w_idx));
+                let w1 = _mm256_loadu_pd(cut.get_unchecked(w_idx + 4));
+                acc[0] = _mm256_fmadd_pd(w0, s0, acc[0]);
+                acc[1] = _mm256_fmadd_pd(w1, s1, acc[1]);
+            }
+            w_idx += 8;
+            s_idx += 2;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_pd(acc[0], acc[1]);
+        }
+        result
+    }
+
+    #[target_feature(enable = "avx", enable = "fma")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f64],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let mut accs = [[_mm256_setzero_pd(); 2]; N];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let w0 = _mm256_loadu_pd(wave_cut.get_unchecked(w_idx));
+            let w1 = _mm256_loadu_pd(wave_cut.get_unchecked(w_idx + 4));
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                acc[0] = _mm256_fmadd_pd(w0, *sinc.get_unchecked(s_idx), acc[0]);
+                acc[1] = _mm256_fmadd_pd(w1, *sinc.get_unchecked(s_idx + 1), acc[1]);
+            }
+            w_idx += 8;
+            s_idx += 2;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_pd(acc[0], acc[1]);
+        }
         result
     }
 }
@@ -159,6 +322,34 @@ impl<T> SincInterpolator<T> for AvxInterpolator<T> where T: AvxSample {
     fn nbr_sincs(&self) -> usize {
         self.nbr_sincs
     }
+
+    /// Calculate the scalar products for several channels, in blocks of `CHANNEL_BLOCK` channels
+    fn get_sinc_interpolated_multi(&self, waves: &[&[T]], index: usize, subindex: usize, output: &mut [T]) {
+        check_multi(waves, output, index, subindex, self.length, self.nbr_sincs);
+        let mut blocks = waves.chunks_exact(CHANNEL_BLOCK);
+        let mut outputs = output.chunks_exact_mut(CHANNEL_BLOCK);
+        for (block, out) in (&mut blocks).zip(&mut outputs) {
+            out.copy_from_slice(unsafe { &T::get_sinc_interpolated_block_unsafe(block, index, subindex, &self.sincs, self.length) });
+        }
+        for (wave, out) in blocks.remainder().iter().zip(outputs.into_remainder().iter_mut()) {
+            *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, subindex, &self.sincs, self.length) };
+        }
+    }
+
+    /// Calculate the scalar products for several subindices, reading the waveform only once
+    fn get_sinc_interpolated_phases(&self, wave: &[T], index: usize, subindices: &[usize], output: &mut [T]) {
+        check_phases(wave, output, index, subindices, self.length, self.nbr_sincs);
+        match subindices.len() {
+            2 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<2>(wave, index, subindices, &self.sincs, self.length) }),
+            4 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<4>(wave, index, subindices, &self.sincs, self.length) }),
+            6 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<6>(wave, index, subindices, &self.sincs, self.length) }),
+            _ => {
+                for (subindex, out) in subindices.iter().zip(output.iter_mut()) {
+                    *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, *subindex, &self.sincs, self.length) };
+                }
+            }
+        }
+    }
 }
 
 impl<T> AvxInterpolator<T> where T: Sample {
@@ -193,7 +384,9 @@ impl<T> AvxInterpolator<T> where T: Sample {
 
 #[cfg(test)]
 mod tests {
-    use crate::asynchro::SincInterpolator;
+    use crate::asynchro::{
+        assert_multi_matches_single, assert_phases_match_single, SincInterpolator,
+    };
     use crate::interpolator_avx::AvxInterpolator;
     use crate::sinc::make_sincs;
     use crate::WindowFunction;
@@ -216,7 +409,7 @@ mod tests {
             wave.push(rng.gen::<f64>());
         }
         let sinc_len = 256;
-        let f_cutoff = 0.9473371669037001;
+        let f_cutoff = 0.947_337_15;
         let oversampling_factor = 256;
         let window = WindowFunction::BlackmanHarris2;
         let sincs = make_sincs::<f64>(sinc_len, oversampling_factor, f_cutoff, window);
@@ -242,7 +435,7 @@ mod tests {
             wave.push(rng.gen::<f32>());
         }
         let sinc_len = 256;
-        let f_cutoff = 0.9473371669037001;
+        let f_cutoff = 0.947_337_15;
         let oversampling_factor = 256;
         let window = WindowFunction::BlackmanHarris2;
         let sincs = make_sincs::<f32>(sinc_len, oversampling_factor, f_cutoff, window);
@@ -259,4 +452,52 @@ mod tests {
         let check = get_sinc_interpolated(&wave, 333, &sincs[123]);
         assert!((value - check).abs() < 1.0e-5);
     }
+
+    #[test]
+    fn test_avx_interpolator_multi_64() {
+        let interpolator = match AvxInterpolator::<f64>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2) {
+            Ok(interpolator) => interpolator,
+            Err(..) => {
+                assert!(!(is_x86_feature_detected!("avx") && is_x86_feature_detected!("fma")));
+                return;
+            },
+        };
+        assert_multi_matches_single::<f64, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_avx_interpolator_multi_32() {
+        let interpolator = match AvxInterpolator::<f32>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2) {
+            Ok(interpolator) => interpolator,
+            Err(..) => {
+                assert!(!(is_x86_feature_detected!("avx") && is_x86_feature_detected!("fma")));
+                return;
+            },
+        };
+        assert_multi_matches_single::<f32, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_avx_interpolator_phases_64() {
+        let interpolator = match AvxInterpolator::<f64>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2) {
+            Ok(interpolator) => interpolator,
+            Err(..) => {
+                assert!(!(is_x86_feature_detected!("avx") && is_x86_feature_detected!("fma")));
+                return;
+            },
+        };
+        assert_phases_match_single::<f64, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_avx_interpolator_phases_32() {
+        let interpolator = match AvxInterpolator::<f32>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2) {
+            Ok(interpolator) => interpolator,
+            Err(..) => {
+                assert!(!(is_x86_feature_detected!("avx") && is_x86_feature_detected!("fma")));
+                return;
+            },
+        };
+        assert_phases_match_single::<f32, _>(&interpolator);
+    }
 }
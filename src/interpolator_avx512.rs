@@ -0,0 +1,435 @@
+// The AVX-512 intrinsics are stable since Rust 1.89, which the `avx512` feature requires.
+#![allow(clippy::incompatible_msrv)]
+
+use crate::sinc::make_sincs;
+use crate::windows::WindowFunction;
+use crate::error::{MissingCpuFeature, CpuFeature};
+use core::arch::x86_64::{__m512, __m512d, __mmask16};
+use core::arch::x86_64::{_mm512_fmadd_pd, _mm512_loadu_pd, _mm512_reduce_add_pd, _mm512_setzero_pd};
+use core::arch::x86_64::{
+    _mm512_fmadd_ps, _mm512_loadu_ps, _mm512_maskz_loadu_ps, _mm512_reduce_add_ps, _mm512_setzero_ps,
+};
+use crate::asynchro::{check_multi, check_phases, cut_block, SincInterpolator, CHANNEL_BLOCK};
+use crate::Sample;
+
+/// Collection of cpu features required for this interpolator.
+static FEATURES: &[CpuFeature] = &[CpuFeature::Avx512f];
+
+/// Mask selecting the lower half of a vector of 16 f32 values.
+/// The sinc length is a multiple of 8, so a partial vector at the end is always half full.
+const LOWER_HALF: __mmask16 = 0x00ff;
+
+/// Trait governing what can be done with an Avx512Sample.
+pub trait Avx512Sample: Sized + Copy {
+    type Sinc: Send + Sync;
+
+    /// Pack sincs into a vector.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because it uses target_enable dispatching. There are no
+    /// special requirements from the caller.
+    unsafe fn pack_sincs(sincs: Vec<Vec<Self>>) -> Vec<Vec<Self::Sinc>>;
+
+    /// Interpolate a sinc sample.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs.
+    unsafe fn get_sinc_interpolated_unsafe(
+        wave: &[Self],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> Self;
+
+    /// Interpolate a sinc sample for a block of `CHANNEL_BLOCK` channels,
+    /// loading each part of the sinc only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `waves` contains `CHANNEL_BLOCK` channels.
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[Self]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; CHANNEL_BLOCK];
+
+    /// Interpolate a sinc sample for `N` subindices at the same index,
+    /// loading each part of the waveform only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `subindices` contains `N` values.
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[Self],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; N];
+}
+
+/// Load 16 values from a waveform, or only the lower 8 if `half` is set.
+#[target_feature(enable = "avx512f")]
+unsafe fn load_ps(wave: &[f32], w_idx: usize, half: bool) -> __m512 {
+    if half {
+        _mm512_maskz_loadu_ps(LOWER_HALF, wave.get_unchecked(w_idx))
+    } else {
+        _mm512_loadu_ps(wave.get_unchecked(w_idx))
+    }
+}
+
+impl Avx512Sample for f32 {
+    type Sinc = __m512;
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn pack_sincs(sincs: Vec<Vec<Self>>) -> Vec<Vec<Self::Sinc>> {
+        let mut packed_sincs = Vec::new();
+        for sinc in sincs.iter() {
+            let mut packed = Vec::new();
+            for elements in sinc.chunks(16) {
+                // the last chunk may be half full, pad it with zeros
+                let mut padded = [0.0; 16];
+                padded[..elements.len()].copy_from_slice(elements);
+                let packed_elems = _mm512_loadu_ps(&padded[0]);
+                packed.push(packed_elems);
+            }
+            packed_sincs.push(packed);
+        }
+        packed_sincs
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn get_sinc_interpolated_unsafe(
+        wave: &[f32],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> f32 {
+        let sinc = sincs.get_unchecked(subindex);
+        let wave_cut = &wave[index..(index + length)];
+        let mut acc = _mm512_setzero_ps();
+        let mut w_idx = 0;
+        for s_idx in 0..(length + 15) / 16 {
+            let w = load_ps(wave_cut, w_idx, w_idx + 16 > length);
+            acc = _mm512_fmadd_ps(w, *sinc.get_unchecked(s_idx), acc);
+            w_idx += 16;
+        }
+        _mm512_reduce_add_ps(acc)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f32]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let mut accs = [_mm512_setzero_ps(); CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        for s_idx in 0..(length + 15) / 16 {
+            let s = *sinc.get_unchecked(s_idx);
+            let half = w_idx + 16 > length;
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w = load_ps(cut, w_idx, half);
+                *acc = _mm512_fmadd_ps(w, s, *acc);
+            }
+            w_idx += 16;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = _mm512_reduce_add_ps(*acc);
+        }
+        result
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f32],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let mut accs = [_mm512_setzero_ps(); N];
+        let mut w_idx = 0;
+        for s_idx in 0..(length + 15) / 16 {
+            let w = load_ps(wave_cut, w_idx, w_idx + 16 > length);
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                *acc = _mm512_fmadd_ps(w, *sinc.get_unchecked(s_idx), *acc);
+            }
+            w_idx += 16;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = _mm512_reduce_add_ps(*acc);
+        }
+        result
+    }
+}
+
+impl Avx512Sample for f64 {
+    type Sinc = __m512d;
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn pack_sincs(sincs: Vec<Vec<f64>>) -> Vec<Vec<Self::Sinc>> {
+        let mut packed_sincs = Vec::new();
+        for sinc in sincs.iter() {
+            let mut packed = Vec::new();
+            for elements in sinc.chunks(8) {
+                let packed_elems = _mm512_loadu_pd(&elements[0]);
+                packed.push(packed_elems);
+            }
+            packed_sincs.push(packed);
+        }
+        packed_sincs
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn get_sinc_interpolated_unsafe(
+        wave: &[f64],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> f64 {
+        let sinc = sincs.get_unchecked(subindex);
+        let wave_cut = &wave[index..(index + length)];
+        let mut acc = _mm512_setzero_pd();
+        let mut w_idx = 0;
+        for s_idx in 0..length / 8 {
+            let w = _mm512_loadu_pd(wave_cut.get_unchecked(w_idx));
+            acc = _mm512_fmadd_pd(w, *sinc.get_unchecked(s_idx), acc);
+            w_idx += 8;
+        }
+        _mm512_reduce_add_pd(acc)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f64]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let mut accs = [_mm512_setzero_pd(); CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        for s_idx in 0..length / 8 {
+            let s = *sinc.get_unchecked(s_idx);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w = _mm512_loadu_pd(cut.get_unchecked(w_idx));
+                *acc = _mm512_fmadd_pd(w, s, *acc);
+            }
+            w_idx += 8;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = _mm512_reduce_add_pd(*acc);
+        }
+        result
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f64],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let mut accs = [_mm512_setzero_pd(); N];
+        let mut w_idx = 0;
+        for s_idx in 0..length / 8 {
+            let w = _mm512_loadu_pd(wave_cut.get_unchecked(w_idx));
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                *acc = _mm512_fmadd_pd(w, *sinc.get_unchecked(s_idx), *acc);
+            }
+            w_idx += 8;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = _mm512_reduce_add_pd(*acc);
+        }
+        result
+    }
+}
+
+/// An AVX-512 accelerated interpolator
+pub struct Avx512Interpolator<T> where T: Avx512Sample {
+    sincs: Vec<Vec<T::Sinc>>,
+    length: usize,
+    nbr_sincs: usize,
+}
+
+impl<T> SincInterpolator<T> for Avx512Interpolator<T> where T: Avx512Sample {
+    /// Calculate the scalar produt of an input wave and the selected sinc filter
+    fn get_sinc_interpolated(&self, wave: &[T], index: usize, subindex: usize) -> T {
+        assert!((index + self.length) < wave.len(), "Tried to interpolate for index {}, max for the given input is {}", index, wave.len()-self.length-1);
+        assert!(subindex < self.nbr_sincs, "Tried to use sinc subindex {}, max is {}", subindex, self.nbr_sincs-1);
+        unsafe { T::get_sinc_interpolated_unsafe(wave, index, subindex, &self.sincs, self.length) }
+    }
+
+    fn len(&self) -> usize {
+        self.length
+    }
+
+    fn nbr_sincs(&self) -> usize {
+        self.nbr_sincs
+    }
+
+    /// Calculate the scalar products for several channels, in blocks of `CHANNEL_BLOCK` channels
+    fn get_sinc_interpolated_multi(&self, waves: &[&[T]], index: usize, subindex: usize, output: &mut [T]) {
+        check_multi(waves, output, index, subindex, self.length, self.nbr_sincs);
+        let mut blocks = waves.chunks_exact(CHANNEL_BLOCK);
+        let mut outputs = output.chunks_exact_mut(CHANNEL_BLOCK);
+        for (block, out) in (&mut blocks).zip(&mut outputs) {
+            out.copy_from_slice(unsafe { &T::get_sinc_interpolated_block_unsafe(block, index, subindex, &self.sincs, self.length) });
+        }
+        for (wave, out) in blocks.remainder().iter().zip(outputs.into_remainder().iter_mut()) {
+            *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, subindex, &self.sincs, self.length) };
+        }
+    }
+
+    /// Calculate the scalar products for several subindices, reading the waveform only once
+    fn get_sinc_interpolated_phases(&self, wave: &[T], index: usize, subindices: &[usize], output: &mut [T]) {
+        check_phases(wave, output, index, subindices, self.length, self.nbr_sincs);
+        match subindices.len() {
+            2 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<2>(wave, index, subindices, &self.sincs, self.length) }),
+            4 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<4>(wave, index, subindices, &self.sincs, self.length) }),
+            6 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<6>(wave, index, subindices, &self.sincs, self.length) }),
+            _ => {
+                for (subindex, out) in subindices.iter().zip(output.iter_mut()) {
+                    *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, *subindex, &self.sincs, self.length) };
+                }
+            }
+        }
+    }
+}
+
+impl<T> Avx512Interpolator<T> where T: Sample {
+    /// Create a new Avx512Interpolator
+    ///
+    /// Parameters are:
+    /// - `sinc_len`: Length of sinc functions.
+    /// - `oversampling_factor`: Number of intermediate sincs (oversampling factor).
+    /// - `f_cutoff`: Relative cutoff frequency.
+    /// - `window`: Window function to use.
+    pub fn new(
+        sinc_len: usize,
+        oversampling_factor: usize,
+        f_cutoff: f32,
+        window: WindowFunction,
+    ) -> Result<Self, MissingCpuFeature> {
+        if let Some(feature) = FEATURES.iter().find(|f| !f.is_detected()) {
+            return Err(MissingCpuFeature(*feature));
+        }
+
+        assert!(sinc_len % 8 == 0, "Sinc length must be a multiple of 8.");
+        let sincs = make_sincs(sinc_len, oversampling_factor, f_cutoff, window);
+        let sincs = unsafe { <T as Avx512Sample>::pack_sincs(sincs) };
+
+        Ok(Self {
+            sincs,
+            length: sinc_len,
+            nbr_sincs: oversampling_factor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asynchro::{
+        assert_multi_matches_single, assert_phases_match_single, ScalarInterpolator,
+        SincInterpolator,
+    };
+    use crate::interpolator_avx512::Avx512Interpolator;
+    use crate::WindowFunction;
+    use num_traits::Float;
+    use rand::distributions::{Distribution, Standard};
+    use rand::Rng;
+    use std::fmt::Debug;
+
+    fn check_against_scalar<T>(sinc_len: usize, tolerance: T)
+    where
+        T: crate::Sample + Float + Debug,
+        Standard: Distribution<T>,
+    {
+        let interpolator = match Avx512Interpolator::<T>::new(sinc_len, 256, 0.95, WindowFunction::BlackmanHarris2) {
+            Ok(interpolator) => interpolator,
+            Err(..) => {
+                assert!(!is_x86_feature_detected!("avx512f"));
+                return;
+            },
+        };
+        let scalar = ScalarInterpolator::<T>::new(sinc_len, 256, 0.95, WindowFunction::BlackmanHarris2);
+        let mut rng = rand::thread_rng();
+        let wave: Vec<T> = (0..2048).map(|_| rng.gen::<T>()).collect();
+        for subindex in [0, 123, 255].iter() {
+            let value = interpolator.get_sinc_interpolated(&wave, 333, *subindex);
+            let check = scalar.get_sinc_interpolated(&wave, 333, *subindex);
+            assert!((value - check).abs() < tolerance);
+        }
+    }
+
+    #[test]
+    fn test_avx512_interpolator_64() {
+        check_against_scalar::<f64>(256, 1.0e-9);
+    }
+
+    #[test]
+    fn test_avx512_interpolator_32() {
+        check_against_scalar::<f32>(256, 1.0e-5);
+    }
+
+    #[test]
+    fn test_avx512_interpolator_half_vector() {
+        // a sinc length that isn't a multiple of 16 ends with a half-filled vector of f32
+        check_against_scalar::<f32>(72, 1.0e-5);
+        check_against_scalar::<f64>(72, 1.0e-9);
+    }
+
+    #[test]
+    fn test_avx512_interpolator_multi_and_phases() {
+        // include a sinc length that ends with a half-filled vector of f32
+        for sinc_len in [72, 256].iter() {
+            match Avx512Interpolator::<f64>::new(*sinc_len, 256, 0.95, WindowFunction::BlackmanHarris2) {
+                Ok(interpolator) => {
+                    assert_multi_matches_single::<f64, _>(&interpolator);
+                    assert_phases_match_single::<f64, _>(&interpolator);
+                }
+                Err(..) => assert!(!is_x86_feature_detected!("avx512f")),
+            }
+            match Avx512Interpolator::<f32>::new(*sinc_len, 256, 0.95, WindowFunction::BlackmanHarris2) {
+                Ok(interpolator) => {
+                    assert_multi_matches_single::<f32, _>(&interpolator);
+                    assert_phases_match_single::<f32, _>(&interpolator);
+                }
+                Err(..) => assert!(!is_x86_feature_detected!("avx512f")),
+            }
+        }
+    }
+}
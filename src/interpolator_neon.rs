@@ -1,4 +1,4 @@
-use crate::asynchro::SincInterpolator;
+use crate::asynchro::{check_multi, check_phases, cut_block, SincInterpolator, CHANNEL_BLOCK};
 use crate::sinc::make_sincs;
 use crate::windows::WindowFunction;
 use core::arch::aarch64::{float32x4_t, float64x2_t};
@@ -11,8 +11,8 @@ use crate::Sample;
 static FEATURES: &[CpuFeature] = &[CpuFeature::Neon];
 
 /// Trait governing what can be done with an NeonSample.
-pub trait NeonSample: Sized {
-    type Sinc;
+pub trait NeonSample: Sized + Copy {
+    type Sinc: Send + Sync;
 
     /// Pack sincs into a vector.
     /// 
@@ -35,6 +35,54 @@ pub trait NeonSample: Sized {
         sincs: &[Vec<Self::Sinc>],
         length: usize,
     ) -> Self;
+
+    /// Interpolate a sinc sample for a block of `CHANNEL_BLOCK` channels,
+    /// loading each part of the sinc only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `waves` contains `CHANNEL_BLOCK` channels.
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[Self]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; CHANNEL_BLOCK];
+
+    /// Interpolate a sinc sample for `N` subindices at the same index,
+    /// loading each part of the waveform only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `subindices` contains `N` values.
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[Self],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; N];
+}
+
+/// Sum the elements of a pair of accumulators.
+#[target_feature(enable = "neon")]
+unsafe fn sum_f32(acc0: float32x4_t, acc1: float32x4_t) -> f32 {
+    let packedsum = vaddq_f32(acc0, acc1);
+    let array = core::slice::from_raw_parts(&packedsum as *const _ as *const f32, 4);
+    array[0] + array[1] + array[2] + array[3]
+}
+
+/// Sum the elements of four accumulators.
+#[target_feature(enable = "neon")]
+unsafe fn sum_f64(acc: &[float64x2_t; 4]) -> f64 {
+    let packedsum0 = vaddq_f64(acc[0], acc[1]);
+    let packedsum1 = vaddq_f64(acc[2], acc[3]);
+    let packedsum2 = vaddq_f64(packedsum0, packedsum1);
+    let values = core::slice::from_raw_parts(&packedsum2 as *const _ as *const f64, 2);
+    values[0] + values[1]
 }
 
 impl NeonSample for f32 {
@@ -78,9 +126,74 @@ impl NeonSample for f32 {
             w_idx += 8;
             s_idx += 2;
         }
-        let packedsum = vaddq_f32(acc0, acc1);
-	let array = core::slice::from_raw_parts(&packedsum as *const _ as *const f32 , 4);
-        array[0] + array[1] + array[2] + array[3]
+        sum_f32(acc0, acc1)
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f32]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let zero = vld1q_dup_f32(&0.0);
+        let mut accs = [[zero; 2]; CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let sinc0 = *sinc.get_unchecked(s_idx);
+            let sinc1 = *sinc.get_unchecked(s_idx + 1);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w0 = vld1q_f32(cut.get_unchecked(w_idx));
+                let w1 = vld1q_f32(cut.get_unchecked(w_idx + 4));
+                acc[0] = vaddq_f32(acc[0], vmulq_f32(w0, sinc0));
+                acc[1] = vaddq_f32(acc[1], vmulq_f32(w1, sinc1));
+            }
+            w_idx += 8;
+            s_idx += 2;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_f32(acc[0], acc[1]);
+        }
+        result
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f32],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let zero = vld1q_dup_f32(&0.0);
+        let mut accs = [[zero; 2]; N];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let w0 = vld1q_f32(wave_cut.get_unchecked(w_idx));
+            let w1 = vld1q_f32(wave_cut.get_unchecked(w_idx + 4));
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                acc[0] = vaddq_f32(acc[0], vmulq_f32(w0, *sinc.get_unchecked(s_idx)));
+                acc[1] = vaddq_f32(acc[1], vmulq_f32(w1, *sinc.get_unchecked(s_idx + 1)));
+            }
+            w_idx += 8;
+            s_idx += 2;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_f32(acc[0], acc[1]);
+        }
+        result
     }
 }
 
@@ -133,11 +246,84 @@ impl NeonSample for f64 {
             w_idx += 8;
             s_idx += 4;
         }
-        let packedsum0 = vaddq_f64(acc0, acc1);
-        let packedsum1 = vaddq_f64(acc2, acc3);
-        let packedsum2 = vaddq_f64(packedsum0, packedsum1);
-        let values = core::slice::from_raw_parts(&packedsum2 as *const _ as *const f64 , 2);
-        values[0] + values[1]
+        sum_f64(&[acc0, acc1, acc2, acc3])
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f64]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let zero = vld1q_f64([0.0, 0.0].as_ptr());
+        let mut accs = [[zero; 4]; CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let sinc0 = *sinc.get_unchecked(s_idx);
+            let sinc1 = *sinc.get_unchecked(s_idx + 1);
+            let sinc2 = *sinc.get_unchecked(s_idx + 2);
+            let sinc3 = *sinc.get_unchecked(s_idx + 3);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w0 = vld1q_f64(cut.get_unchecked(w_idx));
+                let w1 = vld1q_f64(cut.get_unchecked(w_idx + 2));
+                let w2 = vld1q_f64(cut.get_unchecked(w_idx + 4));
+                let w3 = vld1q_f64(cut.get_unchecked(w_idx + 6));
+                acc[0] = vaddq_f64(acc[0], vmulq_f64(w0, sinc0));
+                acc[1] = vaddq_f64(acc[1], vmulq_f64(w1, sinc1));
+                acc[2] = vaddq_f64(acc[2], vmulq_f64(w2, sinc2));
+                acc[3] = vaddq_f64(acc[3], vmulq_f64(w3, sinc3));
+            }
+            w_idx += 8;
+            s_idx += 4;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_f64(acc);
+        }
+        result
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f64],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let zero = vld1q_f64([0.0, 0.0].as_ptr());
+        let mut accs = [[zero; 4]; N];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let w0 = vld1q_f64(wave_cut.get_unchecked(w_idx));
+            let w1 = vld1q_f64(wave_cut.get_unchecked(w_idx + 2));
+            let w2 = vld1q_f64(wave_cut.get_unchecked(w_idx + 4));
+            let w3 = vld1q_f64(wave_cut.get_unchecked(w_idx + 6));
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                acc[0] = vaddq_f64(acc[0], vmulq_f64(w0, *sinc.get_unchecked(s_idx)));
+                acc[1] = vaddq_f64(acc[1], vmulq_f64(w1, *sinc.get_unchecked(s_idx + 1)));
+                acc[2] = vaddq_f64(acc[2], vmulq_f64(w2, *sinc.get_unchecked(s_idx + 2)));
+                acc[3] = vaddq_f64(acc[3], vmulq_f64(w3, *sinc.get_unchecked(s_idx + 3)));
+            }
+            w_idx += 8;
+            s_idx += 4;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_f64(acc);
+        }
+        result
     }
 }
 
@@ -163,6 +349,34 @@ impl<T> SincInterpolator<T> for NeonInterpolator<T> where T: Sample {
     fn nbr_sincs(&self) -> usize {
         self.nbr_sincs
     }
+
+    /// Calculate the scalar products for several channels, in blocks of `CHANNEL_BLOCK` channels
+    fn get_sinc_interpolated_multi(&self, waves: &[&[T]], index: usize, subindex: usize, output: &mut [T]) {
+        check_multi(waves, output, index, subindex, self.length, self.nbr_sincs);
+        let mut blocks = waves.chunks_exact(CHANNEL_BLOCK);
+        let mut outputs = output.chunks_exact_mut(CHANNEL_BLOCK);
+        for (block, out) in (&mut blocks).zip(&mut outputs) {
+            out.copy_from_slice(unsafe { &T::get_sinc_interpolated_block_unsafe(block, index, subindex, &self.sincs, self.length) });
+        }
+        for (wave, out) in blocks.remainder().iter().zip(outputs.into_remainder().iter_mut()) {
+            *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, subindex, &self.sincs, self.length) };
+        }
+    }
+
+    /// Calculate the scalar products for several subindices, reading the waveform only once
+    fn get_sinc_interpolated_phases(&self, wave: &[T], index: usize, subindices: &[usize], output: &mut [T]) {
+        check_phases(wave, output, index, subindices, self.length, self.nbr_sincs);
+        match subindices.len() {
+            2 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<2>(wave, index, subindices, &self.sincs, self.length) }),
+            4 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<4>(wave, index, subindices, &self.sincs, self.length) }),
+            6 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<6>(wave, index, subindices, &self.sincs, self.length) }),
+            _ => {
+                for (subindex, out) in subindices.iter().zip(output.iter_mut()) {
+                    *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, *subindex, &self.sincs, self.length) };
+                }
+            }
+        }
+    }
 }
 
 impl<T> NeonInterpolator<T> where T: Sample {
@@ -197,7 +411,9 @@ impl<T> NeonInterpolator<T> where T: Sample {
 
 #[cfg(test)]
 mod tests {
-    use crate::asynchro::SincInterpolator;
+    use crate::asynchro::{
+        assert_multi_matches_single, assert_phases_match_single, SincInterpolator,
+    };
     use crate::interpolator_neon::NeonInterpolator;
     use crate::sinc::make_sincs;
     use crate::WindowFunction;
@@ -249,4 +465,32 @@ mod tests {
         let check = get_sinc_interpolated(&wave, 333, &sincs[123]);
         assert!((value - check).abs() < 1.0e-5);
     }
+
+    #[test]
+    fn test_neon_interpolator_multi_64() {
+        let interpolator =
+            NeonInterpolator::<f64>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2).unwrap();
+        assert_multi_matches_single::<f64, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_neon_interpolator_multi_32() {
+        let interpolator =
+            NeonInterpolator::<f32>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2).unwrap();
+        assert_multi_matches_single::<f32, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_neon_interpolator_phases_64() {
+        let interpolator =
+            NeonInterpolator::<f64>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2).unwrap();
+        assert_phases_match_single::<f64, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_neon_interpolator_phases_32() {
+        let interpolator =
+            NeonInterpolator::<f32>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2).unwrap();
+        assert_phases_match_single::<f32, _>(&interpolator);
+    }
 }
@@ -0,0 +1,517 @@
+use crate::windows::WindowFunction;
+use crate::sinc::make_sincs;
+use core::arch::wasm32::{v128, v128_load};
+use core::arch::wasm32::{f32x4_add, f32x4_extract_lane, f32x4_mul, f32x4_splat};
+use core::arch::wasm32::{f64x2_add, f64x2_extract_lane, f64x2_mul, f64x2_splat};
+use crate::asynchro::{check_multi, check_phases, cut_block, SincInterpolator, CHANNEL_BLOCK};
+use crate::error::{MissingCpuFeature, CpuFeature};
+use crate::Sample;
+
+/// Collection of cpu features required for this interpolator.
+static FEATURES: &[CpuFeature] = &[CpuFeature::Simd128];
+
+/// Trait governing what can be done with a Simd128Sample.
+pub trait Simd128Sample: Sized + Copy {
+    type Sinc: Send + Sync;
+
+    /// Pack sincs into a vector.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because it uses target_enable dispatching. There are no
+    /// special requirements from the caller.
+    unsafe fn pack_sincs(sincs: Vec<Vec<Self>>) -> Vec<Vec<Self::Sinc>>;
+
+    /// Interpolate a sinc sample.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs.
+    unsafe fn get_sinc_interpolated_unsafe(
+        wave: &[Self],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> Self;
+
+    /// Interpolate a sinc sample for a block of `CHANNEL_BLOCK` channels,
+    /// loading each part of the sinc only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `waves` contains `CHANNEL_BLOCK` channels.
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[Self]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; CHANNEL_BLOCK];
+
+    /// Interpolate a sinc sample for `N` subindices at the same index,
+    /// loading each part of the waveform only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `subindices` contains `N` values.
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[Self],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; N];
+}
+
+/// Load a vector from a slice, starting at `idx`.
+#[target_feature(enable = "simd128")]
+unsafe fn load<T>(values: &[T], idx: usize) -> v128 {
+    v128_load(values.get_unchecked(idx) as *const T as *const v128)
+}
+
+/// Sum the elements of a pair of accumulators.
+#[target_feature(enable = "simd128")]
+unsafe fn sum_f32(acc0: v128, acc1: v128) -> f32 {
+    let temp4 = f32x4_add(acc0, acc1);
+    (f32x4_extract_lane::<0>(temp4) + f32x4_extract_lane::<1>(temp4))
+        + (f32x4_extract_lane::<2>(temp4) + f32x4_extract_lane::<3>(temp4))
+}
+
+/// Sum the elements of four accumulators.
+#[target_feature(enable = "simd128")]
+unsafe fn sum_f64(acc: &[v128; 4]) -> f64 {
+    let temp2_0 = f64x2_add(acc[0], acc[1]);
+    let temp2_1 = f64x2_add(acc[2], acc[3]);
+    let temp2 = f64x2_add(temp2_0, temp2_1);
+    f64x2_extract_lane::<0>(temp2) + f64x2_extract_lane::<1>(temp2)
+}
+
+impl Simd128Sample for f32 {
+    type Sinc = v128;
+
+    #[target_feature(enable = "simd128")]
+    unsafe fn pack_sincs(sincs: Vec<Vec<Self>>) -> Vec<Vec<Self::Sinc>> {
+        let mut packed_sincs = Vec::new();
+        for sinc in sincs.iter() {
+            let mut packed = Vec::new();
+            for elements in sinc.chunks(4) {
+                let packed_elems = load(elements, 0);
+                packed.push(packed_elems);
+            }
+            packed_sincs.push(packed);
+        }
+        packed_sincs
+    }
+
+    #[target_feature(enable = "simd128")]
+    unsafe fn get_sinc_interpolated_unsafe(
+        wave: &[f32],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> f32 {
+        let sinc = sincs.get_unchecked(subindex);
+        let wave_cut = &wave[index..(index + length)];
+        let mut acc0 = f32x4_splat(0.0);
+        let mut acc1 = f32x4_splat(0.0);
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..wave_cut.len() / 8 {
+            let w0 = load(wave_cut, w_idx);
+            let w1 = load(wave_cut, w_idx + 4);
+            let s0 = f32x4_mul(w0, *sinc.get_unchecked(s_idx));
+            let s1 = f32x4_mul(w1, *sinc.get_unchecked(s_idx + 1));
+            acc0 = f32x4_add(acc0, s0);
+            acc1 = f32x4_add(acc1, s1);
+            w_idx += 8;
+            s_idx += 2;
+        }
+        sum_f32(acc0, acc1)
+    }
+
+    #[target_feature(enable = "simd128")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f32]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let mut accs = [[f32x4_splat(0.0); 2]; CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let sinc0 = *sinc.get_unchecked(s_idx);
+            let sinc1 = *sinc.get_unchecked(s_idx + 1);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w0 = load(cut, w_idx);
+                let w1 = load(cut, w_idx + 4);
+                acc[0] = f32x4_add(acc[0], f32x4_mul(w0, sinc0));
+                acc[1] = f32x4_add(acc[1], f32x4_mul(w1, sinc1));
+            }
+            w_idx += 8;
+            s_idx += 2;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_f32(acc[0], acc[1]);
+        }
+        result
+    }
+
+    #[target_feature(enable = "simd128")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f32],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let mut accs = [[f32x4_splat(0.0); 2]; N];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let w0 = load(wave_cut, w_idx);
+            let w1 = load(wave_cut, w_idx + 4);
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                acc[0] = f32x4_add(acc[0], f32x4_mul(w0, *sinc.get_unchecked(s_idx)));
+                acc[1] = f32x4_add(acc[1], f32x4_mul(w1, *sinc.get_unchecked(s_idx + 1)));
+            }
+            w_idx += 8;
+            s_idx += 2;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_f32(acc[0], acc[1]);
+        }
+        result
+    }
+}
+
+impl Simd128Sample for f64 {
+    type Sinc = v128;
+
+    #[target_feature(enable = "simd128")]
+    unsafe fn pack_sincs(sincs: Vec<Vec<f64>>) -> Vec<Vec<Self::Sinc>> {
+        let mut packed_sincs = Vec::new();
+        for sinc in sincs.iter() {
+            let mut packed = Vec::new();
+            for elements in sinc.chunks(2) {
+                let packed_elems = load(elements, 0);
+                packed.push(packed_elems);
+            }
+            packed_sincs.push(packed);
+        }
+        packed_sincs
+    }
+
+    #[target_feature(enable = "simd128")]
+    unsafe fn get_sinc_interpolated_unsafe(
+        wave: &[f64],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> f64 {
+        let sinc = sincs.get_unchecked(subindex);
+        let wave_cut = &wave[index..(index + length)];
+        let mut acc0 = f64x2_splat(0.0);
+        let mut acc1 = f64x2_splat(0.0);
+        let mut acc2 = f64x2_splat(0.0);
+        let mut acc3 = f64x2_splat(0.0);
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..wave_cut.len() / 8 {
+            let w0 = load(wave_cut, w_idx);
+            let w1 = load(wave_cut, w_idx + 2);
+            let w2 = load(wave_cut, w_idx + 4);
+            let w3 = load(wave_cut, w_idx + 6);
+            let s0 = f64x2_mul(w0, *sinc.get_unchecked(s_idx));
+            let s1 = f64x2_mul(w1, *sinc.get_unchecked(s_idx + 1));
+            let s2 = f64x2_mul(w2, *sinc.get_unchecked(s_idx + 2));
+            let s3 = f64x2_mul(w3, *sinc.get_unchecked(s_idx + 3));
+            acc0 = f64x2_add(acc0, s0);
+            acc1 = f64x2_add(acc1, s1);
+            acc2 = f64x2_add(acc2, s2);
+            acc3 = f64x2_add(acc3, s3);
+            w_idx += 8;
+            s_idx += 4;
+        }
+        sum_f64(&[acc0, acc1, acc2, acc3])
+    }
+
+    #[target_feature(enable = "simd128")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f64]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let mut accs = [[f64x2_splat(0.0); 4]; CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let sinc0 = *sinc.get_unchecked(s_idx);
+            let sinc1 = *sinc.get_unchecked(s_idx + 1);
+            let sinc2 = *sinc.get_unchecked(s_idx + 2);
+            let sinc3 = *sinc.get_unchecked(s_idx + 3);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w0 = load(cut, w_idx);
+                let w1 = load(cut, w_idx + 2);
+                let w2 = load(cut, w_idx + 4);
+                let w3 = load(cut, w_idx + 6);
+                acc[0] = f64x2_add(acc[0], f64x2_mul(w0, sinc0));
+                acc[1] = f64x2_add(acc[1], f64x2_mul(w1, sinc1));
+                acc[2] = f64x2_add(acc[2], f64x2_mul(w2, sinc2));
+                acc[3] = f64x2_add(acc[3], f64x2_mul(w3, sinc3));
+            }
+            w_idx += 8;
+            s_idx += 4;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_f64(acc);
+        }
+        result
+    }
+
+    #[target_feature(enable = "simd128")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f64],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let mut accs = [[f64x2_splat(0.0); 4]; N];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let w0 = load(wave_cut, w_idx);
+            let w1 = load(wave_cut, w_idx + 2);
+            let w2 = load(wave_cut, w_idx + 4);
+            let w3 = load(wave_cut, w_idx + 6);
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                acc[0] = f64x2_add(acc[0], f64x2_mul(w0, *sinc.get_unchecked(s_idx)));
+                acc[1] = f64x2_add(acc[1], f64x2_mul(w1, *sinc.get_unchecked(s_idx + 1)));
+                acc[2] = f64x2_add(acc[2], f64x2_mul(w2, *sinc.get_unchecked(s_idx + 2)));
+                acc[3] = f64x2_add(acc[3], f64x2_mul(w3, *sinc.get_unchecked(s_idx + 3)));
+            }
+            w_idx += 8;
+            s_idx += 4;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_f64(acc);
+        }
+        result
+    }
+}
+
+/// A WebAssembly SIMD accelerated interpolator
+pub struct Simd128Interpolator<T> where T: Simd128Sample {
+    sincs: Vec<Vec<T::Sinc>>,
+    length: usize,
+    nbr_sincs: usize,
+}
+
+impl<T> SincInterpolator<T> for Simd128Interpolator<T> where T: Simd128Sample {
+    /// Calculate the scalar produt of an input wave and the selected sinc filter
+    fn get_sinc_interpolated(&self, wave: &[T], index: usize, subindex: usize) -> T {
+        assert!((index + self.length) < wave.len(), "Tried to interpolate for index {}, max for the given input is {}", index, wave.len()-self.length-1);
+        assert!(subindex < self.nbr_sincs, "Tried to use sinc subindex {}, max is {}", subindex, self.nbr_sincs-1);
+        unsafe { T::get_sinc_interpolated_unsafe(wave, index, subindex, &self.sincs, self.length) }
+    }
+
+    fn len(&self) -> usize {
+        self.length
+    }
+
+    fn nbr_sincs(&self) -> usize {
+        self.nbr_sincs
+    }
+
+    /// Calculate the scalar products for several channels, in blocks of `CHANNEL_BLOCK` channels
+    fn get_sinc_interpolated_multi(&self, waves: &[&[T]], index: usize, subindex: usize, output: &mut [T]) {
+        check_multi(waves, output, index, subindex, self.length, self.nbr_sincs);
+        let mut blocks = waves.chunks_exact(CHANNEL_BLOCK);
+        let mut outputs = output.chunks_exact_mut(CHANNEL_BLOCK);
+        for (block, out) in (&mut blocks).zip(&mut outputs) {
+            out.copy_from_slice(unsafe { &T::get_sinc_interpolated_block_unsafe(block, index, subindex, &self.sincs, self.length) });
+        }
+        for (wave, out) in blocks.remainder().iter().zip(outputs.into_remainder().iter_mut()) {
+            *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, subindex, &self.sincs, self.length) };
+        }
+    }
+
+    /// Calculate the scalar products for several subindices, reading the waveform only once
+    fn get_sinc_interpolated_phases(&self, wave: &[T], index: usize, subindices: &[usize], output: &mut [T]) {
+        check_phases(wave, output, index, subindices, self.length, self.nbr_sincs);
+        match subindices.len() {
+            2 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<2>(wave, index, subindices, &self.sincs, self.length) }),
+            4 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<4>(wave, index, subindices, &self.sincs, self.length) }),
+            6 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<6>(wave, index, subindices, &self.sincs, self.length) }),
+            _ => {
+                for (subindex, out) in subindices.iter().zip(output.iter_mut()) {
+                    *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, *subindex, &self.sincs, self.length) };
+                }
+            }
+        }
+    }
+}
+
+impl<T> Simd128Interpolator<T> where T: Sample {
+    /// Create a new Simd128Interpolator
+    ///
+    /// Parameters are:
+    /// - `sinc_len`: Length of sinc functions.
+    /// - `oversampling_factor`: Number of intermediate sincs (oversampling factor).
+    /// - `f_cutoff`: Relative cutoff frequency.
+    /// - `window`: Window function to use.
+    pub fn new(
+        sinc_len: usize,
+        oversampling_factor: usize,
+        f_cutoff: f32,
+        window: WindowFunction,
+    ) -> Result<Self, MissingCpuFeature> {
+        if let Some(feature) = FEATURES.iter().find(|f| !f.is_detected()) {
+            return Err(MissingCpuFeature(*feature));
+        }
+
+        assert!(sinc_len % 8 == 0, "Sinc length must be a multiple of 8.");
+        let sincs = make_sincs(sinc_len, oversampling_factor, f_cutoff, window);
+        let sincs = unsafe { <T as Simd128Sample>::pack_sincs(sincs) };
+
+        Ok(Self {
+            sincs,
+            length: sinc_len,
+            nbr_sincs: oversampling_factor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asynchro::{
+        assert_multi_matches_single, assert_phases_match_single, SincInterpolator,
+    };
+    use crate::interpolator_simd128::Simd128Interpolator;
+    use crate::sinc::make_sincs;
+    use crate::WindowFunction;
+    use num_traits::Float;
+    use rand::Rng;
+
+    fn get_sinc_interpolated<T: Float>(wave: &[T], index: usize, sinc: &[T]) -> T {
+        let wave_cut = &wave[index..(index + sinc.len())];
+        wave_cut
+            .iter()
+            .zip(sinc.iter())
+            .fold(T::zero(), |acc, (x, y)| acc + *x * *y)
+    }
+
+    #[test]
+    fn test_simd128_interpolator_64() {
+        let mut rng = rand::thread_rng();
+        let mut wave = Vec::new();
+        for _ in 0..2048 {
+            wave.push(rng.gen::<f64>());
+        }
+        let sinc_len = 256;
+        let f_cutoff = 0.947_337_15;
+        let oversampling_factor = 256;
+        let window = WindowFunction::BlackmanHarris2;
+        let sincs = make_sincs::<f64>(sinc_len, oversampling_factor, f_cutoff, window);
+
+        let interpolator = match Simd128Interpolator::<f64>::new(sinc_len, oversampling_factor, f_cutoff, window) {
+            Ok(interpolator) => interpolator,
+            Err(err) => {
+                if cfg!(target_feature = "simd128") {
+                    panic!("{}", err);
+                }
+                return;
+            },
+        };
+
+        let value = interpolator.get_sinc_interpolated(&wave, 333, 123);
+        let check = get_sinc_interpolated(&wave, 333, &sincs[123]);
+        assert!((value - check).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn test_simd128_interpolator_32() {
+        let mut rng = rand::thread_rng();
+        let mut wave = Vec::new();
+        for _ in 0..2048 {
+            wave.push(rng.gen::<f32>());
+        }
+        let sinc_len = 256;
+        let f_cutoff = 0.947_337_15;
+        let oversampling_factor = 256;
+        let window = WindowFunction::BlackmanHarris2;
+        let sincs = make_sincs::<f32>(sinc_len, oversampling_factor, f_cutoff, window);
+
+        let interpolator = match Simd128Interpolator::<f32>::new(sinc_len, oversampling_factor, f_cutoff, window) {
+            Ok(interpolator) => interpolator,
+            Err(err) => {
+                if cfg!(target_feature = "simd128") {
+                    panic!("{}", err);
+                }
+                return;
+            },
+        };
+
+        let value = interpolator.get_sinc_interpolated(&wave, 333, 123);
+        let check = get_sinc_interpolated(&wave, 333, &sincs[123]);
+        assert!((value - check).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_simd128_interpolator_multi_and_phases_64() {
+        let interpolator = match Simd128Interpolator::<f64>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2) {
+            Ok(interpolator) => interpolator,
+            Err(err) => {
+                if cfg!(target_feature = "simd128") {
+                    panic!("{}", err);
+                }
+                return;
+            },
+        };
+        assert_multi_matches_single::<f64, _>(&interpolator);
+        assert_phases_match_single::<f64, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_simd128_interpolator_multi_and_phases_32() {
+        let interpolator = match Simd128Interpolator::<f32>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2) {
+            Ok(interpolator) => interpolator,
+            Err(err) => {
+                if cfg!(target_feature = "simd128") {
+                    panic!("{}", err);
+                }
+                return;
+            },
+        };
+        assert_multi_matches_single::<f32, _>(&interpolator);
+        assert_phases_match_single::<f32, _>(&interpolator);
+    }
+}
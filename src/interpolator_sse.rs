@@ -3,7 +3,7 @@ use crate::sinc::make_sincs;
 use core::arch::x86_64::{__m128, __m128d};
 use core::arch::x86_64::{_mm_add_pd, _mm_hadd_pd, _mm_loadu_pd, _mm_mul_pd, _mm_setzero_pd, _mm_store_sd};
 use core::arch::x86_64::{_mm_add_ps, _mm_hadd_ps, _mm_loadu_ps, _mm_mul_ps, _mm_setzero_ps, _mm_store_ss};
-use crate::asynchro::SincInterpolator;
+use crate::asynchro::{check_multi, check_phases, cut_block, SincInterpolator, CHANNEL_BLOCK};
 use crate::error::{MissingCpuFeature, CpuFeature};
 use crate::Sample;
 
@@ -11,8 +11,8 @@ use crate::Sample;
 static FEATURES: &[CpuFeature] = &[CpuFeature::Sse3];
 
 /// Trait governing what can be done with an SseSample.
-pub trait SseSample: Sized {
-    type Sinc;
+pub trait SseSample: Sized + Copy {
+    type Sinc: Send + Sync;
 
     /// Pack sincs into a vector.
     /// 
@@ -35,6 +35,59 @@ pub trait SseSample: Sized {
         sincs: &[Vec<Self::Sinc>],
         length: usize,
     ) -> Self;
+
+    /// Interpolate a sinc sample for a block of `CHANNEL_BLOCK` channels,
+    /// loading each part of the sinc only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `waves` contains `CHANNEL_BLOCK` channels.
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[Self]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; CHANNEL_BLOCK];
+
+    /// Interpolate a sinc sample for `N` subindices at the same index,
+    /// loading each part of the waveform only once.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the various indexes are not out of bounds
+    /// in the collection of sincs, and that `subindices` contains `N` values.
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[Self],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [Self; N];
+}
+
+/// Sum the elements of a pair of accumulators.
+#[target_feature(enable = "sse3")]
+unsafe fn sum_ps(acc0: __m128, acc1: __m128) -> f32 {
+    let temp4 = _mm_add_ps(acc0, acc1);
+    let temp2 = _mm_hadd_ps(temp4, temp4);
+    let temp1 = _mm_hadd_ps(temp2, temp2);
+    let mut result = 0.0;
+    _mm_store_ss(&mut result, temp1);
+    result
+}
+
+/// Sum the elements of four accumulators.
+#[target_feature(enable = "sse3")]
+unsafe fn sum_pd(acc: &[__m128d; 4]) -> f64 {
+    let temp2_0 = _mm_add_pd(acc[0], acc[1]);
+    let temp2_1 = _mm_add_pd(acc[2], acc[3]);
+    let temp2 = _mm_hadd_pd(temp2_0, temp2_1);
+    let temp1 = _mm_hadd_pd(temp2, temp2);
+    let mut result = 0.0;
+    _mm_store_sd(&mut result, temp1);
+    result
 }
 
 impl SseSample for f32 {
@@ -78,11 +131,75 @@ impl SseSample for f32 {
             w_idx += 8;
             s_idx += 2;
         }
-        let temp4 = _mm_add_ps(acc0, acc1);
-        let temp2 = _mm_hadd_ps(temp4, temp4);
-        let temp1 = _mm_hadd_ps(temp2, temp2);
-        let mut result = 0.0;
-        _mm_store_ss(&mut result, temp1);
+        sum_ps(acc0, acc1)
+    }
+
+    #[target_feature(enable = "sse3")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f32]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let mut accs = [[_mm_setzero_ps(); 2]; CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let sinc0 = *sinc.get_unchecked(s_idx);
+            let sinc1 = *sinc.get_unchecked(s_idx + 1);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w0 = _mm_loadu_ps(cut.get_unchecked(w_idx));
+                let w1 = _mm_loadu_ps(cut.get_unchecked(w_idx + 4));
+                let s0 = _mm_mul_ps(w0, sinc0);
+                let s1 = _mm_mul_ps(w1, sinc1);
+                acc[0] = _mm_add_ps(acc[0], s0);
+                acc[1] = _mm_add_ps(acc[1], s1);
+            }
+            w_idx += 8;
+            s_idx += 2;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_ps(acc[0], acc[1]);
+        }
+        result
+    }
+
+    #[target_feature(enable = "sse3")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f32],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f32; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let mut accs = [[_mm_setzero_ps(); 2]; N];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let w0 = _mm_loadu_ps(wave_cut.get_unchecked(w_idx));
+            let w1 = _mm_loadu_ps(wave_cut.get_unchecked(w_idx + 4));
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                let s0 = _mm_mul_ps(w0, *sinc.get_unchecked(s_idx));
+                let s1 = _mm_mul_ps(w1, *sinc.get_unchecked(s_idx + 1));
+                acc[0] = _mm_add_ps(acc[0], s0);
+                acc[1] = _mm_add_ps(acc[1], s1);
+            }
+            w_idx += 8;
+            s_idx += 2;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_ps(acc[0], acc[1]);
+        }
         result
     }
 }
@@ -136,12 +253,81 @@ impl SseSample for f64 {
             w_idx += 8;
             s_idx += 4;
         }
-        let temp2_0 = _mm_add_pd(acc0, acc1);
-        let temp2_1 = _mm_add_pd(acc2, acc3);
-        let temp2 = _mm_hadd_pd(temp2_0, temp2_1);
-        let temp1 = _mm_hadd_pd(temp2, temp2);
-        let mut result = 0.0;
-        _mm_store_sd(&mut result, temp1);
+        sum_pd(&[acc0, acc1, acc2, acc3])
+    }
+
+    #[target_feature(enable = "sse3")]
+    unsafe fn get_sinc_interpolated_block_unsafe(
+        waves: &[&[f64]],
+        index: usize,
+        subindex: usize,
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; CHANNEL_BLOCK] {
+        let sinc = sincs.get_unchecked(subindex);
+        let cuts = cut_block(waves, index, length);
+        let mut accs = [[_mm_setzero_pd(); 4]; CHANNEL_BLOCK];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let sinc0 = *sinc.get_unchecked(s_idx);
+            let sinc1 = *sinc.get_unchecked(s_idx + 1);
+            let sinc2 = *sinc.get_unchecked(s_idx + 2);
+            let sinc3 = *sinc.get_unchecked(s_idx + 3);
+            for (acc, cut) in accs.iter_mut().zip(cuts.iter()) {
+                let w0 = _mm_loadu_pd(cut.get_unchecked(w_idx));
+                let w1 = _mm_loadu_pd(cut.get_unchecked(w_idx + 2));
+                let w2 = _mm_loadu_pd(cut.get_unchecked(w_idx + 4));
+                let w3 = _mm_loadu_pd(cut.get_unchecked(w_idx + 6));
+                acc[0] = _mm_add_pd(acc[0], _mm_mul_pd(w0, sinc0));
+                acc[1] = _mm_add_pd(acc[1], _mm_mul_pd(w1, sinc1));
+                acc[2] = _mm_add_pd(acc[2], _mm_mul_pd(w2, sinc2));
+                acc[3] = _mm_add_pd(acc[3], _mm_mul_pd(w3, sinc3));
+            }
+            w_idx += 8;
+            s_idx += 4;
+        }
+        let mut result = [0.0; CHANNEL_BLOCK];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_pd(acc);
+        }
+        result
+    }
+
+    #[target_feature(enable = "sse3")]
+    unsafe fn get_sinc_interpolated_phases_unsafe<const N: usize>(
+        wave: &[f64],
+        index: usize,
+        subindices: &[usize],
+        sincs: &[Vec<Self::Sinc>],
+        length: usize,
+    ) -> [f64; N] {
+        let mut phase_sincs: [&[Self::Sinc]; N] = [&[]; N];
+        for (sinc, subindex) in phase_sincs.iter_mut().zip(subindices.iter()) {
+            *sinc = sincs.get_unchecked(*subindex);
+        }
+        let wave_cut = &wave[index..(index + length)];
+        let mut accs = [[_mm_setzero_pd(); 4]; N];
+        let mut w_idx = 0;
+        let mut s_idx = 0;
+        for _ in 0..length / 8 {
+            let w0 = _mm_loadu_pd(wave_cut.get_unchecked(w_idx));
+            let w1 = _mm_loadu_pd(wave_cut.get_unchecked(w_idx + 2));
+            let w2 = _mm_loadu_pd(wave_cut.get_unchecked(w_idx + 4));
+            let w3 = _mm_loadu_pd(wave_cut.get_unchecked(w_idx + 6));
+            for (acc, sinc) in accs.iter_mut().zip(phase_sincs.iter()) {
+                acc[0] = _mm_add_pd(acc[0], _mm_mul_pd(w0, *sinc.get_unchecked(s_idx)));
+                acc[1] = _mm_add_pd(acc[1], _mm_mul_pd(w1, *sinc.get_unchecked(s_idx + 1)));
+                acc[2] = _mm_add_pd(acc[2], _mm_mul_pd(w2, *sinc.get_unchecked(s_idx + 2)));
+                acc[3] = _mm_add_pd(acc[3], _mm_mul_pd(w3, *sinc.get_unchecked(s_idx + 3)));
+            }
+            w_idx += 8;
+            s_idx += 4;
+        }
+        let mut result = [0.0; N];
+        for (res, acc) in result.iter_mut().zip(accs.iter()) {
+            *res = sum_pd(acc);
+        }
         result
     }
 }
@@ -168,6 +354,34 @@ impl<T> SincInterpolator<T> for SseInterpolator<T> where T: SseSample {
     fn nbr_sincs(&self) -> usize {
         self.nbr_sincs
     }
+
+    /// Calculate the scalar products for several channels, in blocks of `CHANNEL_BLOCK` channels
+    fn get_sinc_interpolated_multi(&self, waves: &[&[T]], index: usize, subindex: usize, output: &mut [T]) {
+        check_multi(waves, output, index, subindex, self.length, self.nbr_sincs);
+        let mut blocks = waves.chunks_exact(CHANNEL_BLOCK);
+        let mut outputs = output.chunks_exact_mut(CHANNEL_BLOCK);
+        for (block, out) in (&mut blocks).zip(&mut outputs) {
+            out.copy_from_slice(unsafe { &T::get_sinc_interpolated_block_unsafe(block, index, subindex, &self.sincs, self.length) });
+        }
+        for (wave, out) in blocks.remainder().iter().zip(outputs.into_remainder().iter_mut()) {
+            *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, subindex, &self.sincs, self.length) };
+        }
+    }
+
+    /// Calculate the scalar products for several subindices, reading the waveform only once
+    fn get_sinc_interpolated_phases(&self, wave: &[T], index: usize, subindices: &[usize], output: &mut [T]) {
+        check_phases(wave, output, index, subindices, self.length, self.nbr_sincs);
+        match subindices.len() {
+            2 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<2>(wave, index, subindices, &self.sincs, self.length) }),
+            4 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<4>(wave, index, subindices, &self.sincs, self.length) }),
+            6 => output.copy_from_slice(unsafe { &T::get_sinc_interpolated_phases_unsafe::<6>(wave, index, subindices, &self.sincs, self.length) }),
+            _ => {
+                for (subindex, out) in subindices.iter().zip(output.iter_mut()) {
+                    *out = unsafe { T::get_sinc_interpolated_unsafe(wave, index, *subindex, &self.sincs, self.length) };
+                }
+            }
+        }
+    }
 }
 
 impl<T> SseInterpolator<T> where T: Sample {
@@ -202,7 +416,9 @@ impl<T> SseInterpolator<T> where T: Sample {
 
 #[cfg(test)]
 mod tests {
-    use crate::asynchro::SincInterpolator;
+    use crate::asynchro::{
+        assert_multi_matches_single, assert_phases_match_single, SincInterpolator,
+    };
     use crate::interpolator_sse::SseInterpolator;
     use crate::sinc::make_sincs;
     use crate::WindowFunction;
@@ -225,7 +441,7 @@ mod tests {
             wave.push(rng.gen::<f64>());
         }
         let sinc_len = 256;
-        let f_cutoff = 0.9473371669037001;
+        let f_cutoff = 0.947_337_15;
         let oversampling_factor = 256;
         let window = WindowFunction::BlackmanHarris2;
         let sincs = make_sincs::<f64>(sinc_len, oversampling_factor, f_cutoff, window);
@@ -244,7 +460,7 @@ mod tests {
             wave.push(rng.gen::<f32>());
         }
         let sinc_len = 256;
-        let f_cutoff = 0.9473371669037001;
+        let f_cutoff = 0.947_337_15;
         let oversampling_factor = 256;
         let window = WindowFunction::BlackmanHarris2;
         let sincs = make_sincs::<f32>(sinc_len, oversampling_factor, f_cutoff, window);
@@ -254,4 +470,32 @@ mod tests {
         let check = get_sinc_interpolated(&wave, 333, &sincs[123]);
         assert!((value - check).abs() < 1.0e-5);
     }
+
+    #[test]
+    fn test_sse_interpolator_multi_64() {
+        let interpolator =
+            SseInterpolator::<f64>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2).unwrap();
+        assert_multi_matches_single::<f64, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_sse_interpolator_multi_32() {
+        let interpolator =
+            SseInterpolator::<f32>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2).unwrap();
+        assert_multi_matches_single::<f32, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_sse_interpolator_phases_64() {
+        let interpolator =
+            SseInterpolator::<f64>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2).unwrap();
+        assert_phases_match_single::<f64, _>(&interpolator);
+    }
+
+    #[test]
+    fn test_sse_interpolator_phases_32() {
+        let interpolator =
+            SseInterpolator::<f32>::new(256, 256, 0.95, WindowFunction::BlackmanHarris2).unwrap();
+        assert_phases_match_single::<f32, _>(&interpolator);
+    }
 }
@@ -11,18 +11,104 @@
 //! interpolation filters. The sinc interpolation upsamples by an adjustable factor,
 //! and then the new sample points are calculated by interpolating between these points.
 //! The resampling ratio can be updated at any time.
+//! For effects like tape stops or Doppler shifts, the ratio can also be made to follow a curve
+//! within a chunk, using `set_resample_ratio_curve`.
+//! The ratio can normally change by up to 10% from the original one. Resamplers created with
+//! `new_varispeed` instead allow anything from a quarter to four times the original ratio,
+//! and switch between sinc tables so that the cutoff frequency follows the ratio.
+//! When the input sample rate of a stream changes, `SincFixedOut::reconfigure` switches to a new ratio
+//! and crossfades from the old configuration to the new one, without changing the output chunk size.
+//! 
+//! ## Joining segments
+//! The [Concatenator] joins segments with different sample rates, for example the tracks of a playlist,
+//! into one continuous output stream. Each segment gives exactly the expected number of output frames,
+//! without gaps or overlaps at the joins, and the output is delivered in chunks of a fixed size.
+//!
+//! ## Mixing
+//! The [Mixer] sums several sources with different sample rates into one output stream.
+//! Each source has its own resampler and gain, and for each output chunk the mixer asks every source
+//! for exactly the number of frames that its resampler needs.
+//!
+//! ## Several output rates
+//! The [SincFanOut] resampler converts one input stream to several output sample rates at once.
+//! It gives the same result as one [SincFixedIn] per output rate, but the input is only buffered once
+//! and the sinc tables are shared between the outputs where possible.
+//!
+//! ## Pulling input on demand
+//! The [PullResampler] wraps any resampler and fills output buffers of any length,
+//! calling a closure to fetch input whenever the resampler needs more.
+//! This is convenient in audio device callbacks, where the number of output frames is given.
+//!
+//! ## Streaming
+//! The [StreamResampler] wraps any resampler and accepts any number of input frames per call, for example
+//! from network packets or a decoder, and returns whatever output is available, or blocks of a fixed size.
+//! It buffers input and output internally. With [SincFixedOut], which resamples into a buffer
+//! with [Resampler::process_into_buffer], it does not allocate after it has been created.
+//!
+//! ## Iterator and reader adapters
+//! For simple tools, resampling can be used as a stream transform. The [ResampleIter] adapter wraps
+//! an iterator of frames and yields resampled frames, and the [ResampleReader] adapter wraps
+//! a reader of raw PCM bytes and resamples them on the fly. Both take care of the chunking.
+//! At the end of the input they flush the resampler, and they remove its delay from the start of the output.
+//!
+//! ## Resampling a complete signal
+//! The [resample_buffer] function resamples a complete signal in one call, using either the sinc or the FFT resampler
+//! with a quality preset. It returns exactly `round(len * fs_out / fs_in)` frames, with the delay of the resampler removed.
+//! For offline work on clips that fit in memory, [resample_whole_fft] instead transforms each channel with a single FFT,
+//! truncates or extends the spectrum with an adjustable taper, and transforms it back.
+//! This gives zero-phase output without block artefacts.
+//! Long recordings can be resampled on several threads with [resample_buffer_parallel]. It splits the signal into
+//! overlapping segments and gives output that is bit-identical to resampling with the FFT engine on a single thread.
+//! Only the FFT engine is supported, since the sinc resampler can't be split into segments without changing the output.
+//! With the `parallel` feature the segments are processed on the rayon thread pool, otherwise on threads started for the call.
+//!
+//! ## Command line tool
+//! The crate includes a command line tool for resampling raw audio files, enabled by the `cli` feature.
+//! It lets you choose the resampler type, quality preset, sample format, number of channels and chunk size,
+//! and prints the latency and timing of the resampling. Run it with `--help` to see the options:
+//! ```text
+//! cargo run --release --features cli --bin rubato -- --help
+//! ```
 //!
 //! ## Synchronous resampling
 //! Synchronous resampling is implemented via FFT. The data is FFT:ed, the spectrum modified,
 //! and then inverse FFT:ed to get the resampled data.
 //! This type of resampler is considerably faster but doesn't support changing the resampling ratio.
 //!
+//! ## Multi-stage resampling
+//! For large ratios, such as 384 kHz to 8 kHz, a single sinc filter becomes both long and
+//! costly. The [CascadeFixedIn] resampler instead handles each factor of two with a halfband filter,
+//! and uses a sinc resampler only for the remaining fractional ratio.
+//! The halfband stages are also available on their own as [HalfbandDecimator] and [HalfbandInterpolator],
+//! for example for oversampling by 2 or 4 around a nonlinear process.
+//! They skip the zero taps, use the symmetry of the filter, and use SSE or AVX when available.
+//!
+//! ## Oversampling
+//! The [Oversampler] runs a nonlinear process, such as a saturator or clipper, at 2, 4 or 8 times
+//! the sample rate. It upsamples each chunk with halfband filters, hands the oversampled audio to a closure,
+//! and then downsamples it back. The chunk size is the same for input and output.
+//! 
+//! ## True-peak metering
+//! The [TruePeakMeter] measures the inter-sample peak level of each channel, in dBTP.
+//! By default it uses the 4x oversampling filter given in ITU-R BS.1770,
+//! but any sinc interpolator can be used instead.
+//! 
+//! ## Fractional delay
+//! The [FractionalDelay] delays each channel by an arbitrary, non-integer number of frames,
+//! using the same sinc interpolation as the asynchronous resamplers but without changing the sample rate.
+//! The delays can be changed while running, for example for speaker alignment or Doppler effects.
+//!
 //! ## SIMD acceleration
 //! The asynchronous resampler is designed to benefit from auto-vectorization, meaning that the Rust compiler
 //! can recognize calculations that can be done in parallel. It will then use SIMD instructions for those.
 //! This works quite well, but there is still room for improvement.
 //! On x86_64 it will always use SSE3 if available. The speed benefit compared to auto-vectorization
 //! depends on the CPU, but tends to be in the range 20-30% for 64-bit data, and 50-100% for 32-bit data.
+//! With several channels, the SIMD interpolators process blocks of four channels at once,
+//! loading each part of the sinc filter only once for the whole block. This reduces the memory traffic
+//! for the sinc tables, and gives the same result as processing the channels one by one.
+//! Channels that are not part of a full block instead read the input once for all the
+//! neighboring sincs used by linear, cubic and quintic interpolation.
 //!
 //! ## Cargo features
 //! #### `avx`: AVX on x86_64
@@ -30,6 +116,12 @@
 //! The speed increase compared to SSE depends on the CPU, and tends to range from zero to 50%.
 //! On other architectures than x86_64 the `avx` feature does nothing.
 //!
+//! #### `avx512`: AVX-512 on x86_64
+//! The `avx512` feature enables the use of AVX-512 when the CPU supports it, with AVX, SSE3 and
+//! the scalar interpolator as fallbacks. The vectors hold 16 32-bit floats or 8 64-bit floats,
+//! twice as many as with AVX. The speed increase compared to AVX is largest for 64-bit data.
+//! This feature requires Rust 1.89 or newer, and does nothing on other architectures than x86_64.
+//!
 //! #### `neon`: Experimental Neon support on aarch64
 //! Experimental support for Neon is available for aarch64 (64-bit Arm) by enabling the `neon` feature.
 //! This requires the use of a nightly compiler, as the Neon support in Rust is still experimental.
@@ -37,6 +129,34 @@
 //! compared to the auto-vectorized implementation.
 //! Note that this only works on a full 64-bit operating system.
 //!
+//! #### `simd128`: WebAssembly SIMD on wasm32
+//! The `simd128` feature adds an interpolator using the 128-bit SIMD instructions of WebAssembly.
+//! WebAssembly has no runtime detection of features, so SIMD must also be enabled when compiling,
+//! with `RUSTFLAGS="-C target-feature=+simd128"`. Otherwise the scalar interpolator is used.
+//! The resulting module needs a runtime with SIMD support, which all major browsers have.
+//! The tests can be run with a WASI runtime such as [wasmtime](https://wasmtime.dev/):
+//! ```text
+//! RUSTFLAGS="-C target-feature=+simd128" CARGO_TARGET_WASM32_WASIP1_RUNNER=wasmtime \
+//!     cargo test --target wasm32-wasip1 --features simd128
+//! ```
+//! WASI doesn't support threads, so `resample_buffer_parallel` resamples on the calling thread there.
+//!
+//! #### `cli`: Command line tool
+//! The `cli` feature builds the `rubato` command line tool, see above.
+//!
+//! #### `wav`: WAV file support
+//! The `wav` feature adds functions for reading and writing WAV files, including WAVE_FORMAT_EXTENSIBLE,
+//! with 16, 24 and 32 bit integer and 32 and 64 bit float samples. A whole file can be resampled with
+//! `resample_wav`, which removes the delay of the resampler and keeps the end of the file.
+//!
+//! #### `parallel`: Multi-threaded processing of channels
+//! The `parallel` feature uses [rayon](https://crates.io/crates/rayon) to process the channels of the sinc and FFT
+//! resamplers on several threads. This helps with high channel counts, such as ambisonics or microphone arrays.
+//! The FFT resamplers split the channels into one group per thread, each with its own FFT buffers.
+//! The sinc resamplers process blocks of channels on separate threads, and use a single thread for fewer than 8 channels.
+//! The output is the same as without the feature.
+//! The rayon thread pool is also used by [resample_buffer_parallel].
+//!
 //! ## Documentation
 //!
 //! The full documentation can be generated by rustdoc. To generate and view it run:
@@ -69,23 +189,58 @@
 //!
 //! ## Compatibility
 //!
-//! The `rubato` crate requires rustc version 1.40 or newer.
+//! The `rubato` crate requires rustc version 1.63 or newer.
+//! The `avx512` feature requires rustc 1.89 or newer.
 
 #![cfg_attr(feature = "neon", feature(aarch64_target_feature))]
 #![cfg_attr(feature = "neon", feature(stdsimd))]
 
+mod adapters;
 mod asynchro;
+mod cascade;
+mod concat;
+mod delay;
 mod error;
+mod fanout;
+mod halfband;
+#[cfg(all(target_arch = "x86_64", feature = "avx"))]
+mod halfband_avx;
+#[cfg(target_arch = "x86_64")]
+mod halfband_sse;
 mod interpolation;
+mod mixer;
+mod offline;
+mod oversampler;
+mod pull;
 mod sample;
 mod sinc;
+mod stream;
 mod synchro;
+mod truepeak;
+#[cfg(feature = "wav")]
+mod wav;
 mod windows;
 
+pub use crate::adapters::{ResampleIter, ResampleReader, SampleFormat};
 pub use crate::asynchro::{ScalarInterpolator, SincFixedIn, SincFixedOut};
+pub use crate::cascade::CascadeFixedIn;
+pub use crate::concat::{Concatenator, SegmentResampler};
+pub use crate::delay::FractionalDelay;
 pub use crate::error::{CpuFeature, MissingCpuFeature, ResampleError, ResampleResult};
+pub use crate::fanout::SincFanOut;
+pub use crate::halfband::{HalfbandDecimator, HalfbandInterpolator};
+pub use crate::mixer::Mixer;
+pub use crate::offline::{
+    resample_buffer, resample_buffer_parallel, resample_whole_fft, Engine, Quality,
+};
+pub use crate::oversampler::Oversampler;
+pub use crate::pull::PullResampler;
 pub use crate::sample::Sample;
+pub use crate::stream::StreamResampler;
 pub use crate::synchro::{FftFixedIn, FftFixedInOut, FftFixedOut};
+pub use crate::truepeak::TruePeakMeter;
+#[cfg(feature = "wav")]
+pub use crate::wav::{read_wav, resample_wav, write_wav, WavSpec};
 pub use crate::windows::WindowFunction;
 
 /// Helper macro to define a dummy implementation of the sample trait if a
@@ -120,6 +275,12 @@ interpolator! {
     trait AvxSample;
 }
 
+interpolator! {
+    #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+    mod interpolator_avx512;
+    trait Avx512Sample;
+}
+
 interpolator! {
     #[cfg(target_arch = "x86_64")]
     mod interpolator_sse;
@@ -132,11 +293,17 @@ interpolator! {
     trait NeonSample;
 }
 
+interpolator! {
+    #[cfg(all(target_arch = "wasm32", feature = "simd128"))]
+    mod interpolator_simd128;
+    trait Simd128Sample;
+}
+
 #[macro_use]
 extern crate log;
 
 /// A struct holding the parameters for interpolation.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InterpolationParameters {
     /// Length of the windowed sinc interpolation filter.
     /// Higher values can allow a higher cut-off frequency leading to less high frequency roll-off
@@ -166,8 +333,26 @@ pub struct InterpolationParameters {
 /// It's more efficient to combine the sinc filters with some other interpolation technique.
 /// Then sinc filters are used to provide a fixed number of interpolated points between input samples,
 /// and then the new value is calculated by interpolation between those points.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InterpolationType {
+    /// For quintic interpolation, the six nearest intermediate points are calculated
+    /// using sinc interpolation.
+    /// Then a fifth order polynomial is fitted to these points, and is then used to calculate the new sample value.
+    /// The computation time is about 1.5 times the one for cubic interpolation,
+    /// but the number of intermediate points can be reduced further for the same result.
+    Quintic,
+    /// Like `Quintic`, this uses the six nearest intermediate points.
+    /// Instead of fitting a polynomial through the points, the value is calculated with
+    /// the "optimal" 6-point, 5th order polynomial designed by Olli Niemitalo for 32x oversampled data.
+    /// Since the intermediate points are heavily oversampled, this gives the lowest interpolation error
+    /// of the available types, and allows using the smallest `oversampling_factor` for a given quality.
+    Optimal6,
+    /// Like `Cubic`, this uses the four nearest intermediate points,
+    /// but calculates the new value with the "optimal" 4-point, 2nd order polynomial
+    /// designed by Olli Niemitalo for 32x oversampled data.
+    /// The cost is about the same as for cubic interpolation, but for signals near the
+    /// cutoff frequency the result is somewhat worse. It is mainly included for comparison.
+    Optimal4,
     /// For cubic interpolation, the four nearest intermediate points are calculated
     /// using sinc interpolation.
     /// Then a cubic polynomial is fitted to these points, and is then used to calculate the new sample value.
@@ -202,14 +387,83 @@ pub trait Resampler<T> {
     /// of the vector is itself a vector which contains the samples for a single channel.
     fn process<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<Vec<Vec<T>>>;
 
+    /// Resample a chunk of audio into an existing output buffer.
+    ///
+    /// The input is the same as for "process". The output buffer must have one slice per channel,
+    /// and each channel with input must have room for at least "output_frames_max" frames.
+    /// Returns the number of frames written to each channel.
+    /// The default implementation calls "process" and copies the output.
+    /// # Errors
+    ///
+    /// The function returns the same errors as "process", and an error if the output buffer
+    /// doesn't have the same number of channels as the input, or if a channel is too short.
+    fn process_into_buffer<V: AsRef<[T]>, W: AsMut<[T]>>(
+        &mut self,
+        wave_in: &[V],
+        wave_out: &mut [W],
+    ) -> ResampleResult<usize> {
+        check_output_buffer(wave_in, wave_out, self.output_frames_max())?;
+        let resampled = self.process(wave_in)?;
+        let mut frames = 0;
+        for (wave, out) in resampled.into_iter().zip(wave_out.iter_mut()) {
+            frames = frames.max(wave.len());
+            for (value, sample) in out.as_mut().iter_mut().zip(wave) {
+                *value = sample;
+            }
+        }
+        Ok(frames)
+    }
+
     /// Query for the number of frames needed for the next call to "process".
     fn nbr_frames_needed(&self) -> usize;
 
+    /// Get the largest number of frames that "nbr_frames_needed" can return.
+    /// The default implementation returns "nbr_frames_needed", for resamplers with a fixed input size.
+    fn input_frames_max(&self) -> usize {
+        self.nbr_frames_needed()
+    }
+
+    /// Get the largest number of frames that one call to "process" can return.
+    fn output_frames_max(&self) -> usize;
+
     /// Update the resample ratio.
     fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()>;
 
     /// Update the resample ratio relative to the original one.
     fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()>;
+
+    /// Get the delay of the output relative to the input, in output frames.
+    /// An event at the start of the input appears at this position in the output.
+    /// The default implementation returns zero, for resamplers that don't report a delay.
+    fn output_delay(&self) -> usize {
+        0
+    }
+}
+
+/// Check that an output buffer has the same number of channels as the input,
+/// and room for `frames` frames in each channel with input.
+pub(crate) fn check_output_buffer<V: AsRef<[T]>, W: AsMut<[T]>, T>(
+    wave_in: &[V],
+    wave_out: &mut [W],
+    frames: usize,
+) -> ResampleResult<()> {
+    if wave_out.len() != wave_in.len() {
+        return Err(ResampleError::WrongNumberOfOutputChannels {
+            expected: wave_in.len(),
+            actual: wave_out.len(),
+        });
+    }
+    for (chan, (wave, out)) in wave_in.iter().zip(wave_out.iter_mut()).enumerate() {
+        let out = out.as_mut();
+        if !wave.as_ref().is_empty() && out.len() < frames {
+            return Err(ResampleError::WrongNumberOfOutputFrames {
+                channel: chan,
+                expected: frames,
+                actual: out.len(),
+            });
+        }
+    }
+    Ok(())
 }
 
 pub trait ResamplerFixedOut<T> : Resampler<T>
@@ -233,14 +487,34 @@ pub trait VecResampler<T> {
     /// Input and output data is stored in vectors, where each element contains a vector with all samples for a single channel.
     fn process(&mut self, wave_in: &[Vec<T>]) -> ResampleResult<Vec<Vec<T>>>;
 
+    /// Resample a chunk of audio into an existing output buffer, see [Resampler::process_into_buffer].
+    fn process_into_buffer(
+        &mut self,
+        wave_in: &[Vec<T>],
+        wave_out: &mut [Vec<T>],
+    ) -> ResampleResult<usize>;
+
     /// Query for the number of frames needed for the next call to "process".
     fn nbr_frames_needed(&self) -> usize;
 
+    /// Get the largest number of frames that "nbr_frames_needed" can return.
+    fn input_frames_max(&self) -> usize;
+
+    /// Get the largest number of frames that one call to "process" can return.
+    fn output_frames_max(&self) -> usize;
+
     /// Update the resample ratio.
     fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()>;
 
     /// Update the resample ratio relative to the original one.
     fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()>;
+
+    /// Get the delay of the output relative to the input, in output frames.
+    /// An event at the start of the input appears at this position in the output.
+    /// The default implementation returns zero, for resamplers that don't report a delay.
+    fn output_delay(&self) -> usize {
+        0
+    }
 }
 
 impl<T, U> VecResampler<T> for U
@@ -251,10 +525,26 @@ where
         Resampler::process(self, wave_in)
     }
 
+    fn process_into_buffer(
+        &mut self,
+        wave_in: &[Vec<T>],
+        wave_out: &mut [Vec<T>],
+    ) -> ResampleResult<usize> {
+        Resampler::process_into_buffer(self, wave_in, wave_out)
+    }
+
     fn nbr_frames_needed(&self) -> usize {
         Resampler::nbr_frames_needed(self)
     }
 
+    fn input_frames_max(&self) -> usize {
+        Resampler::input_frames_max(self)
+    }
+
+    fn output_frames_max(&self) -> usize {
+        Resampler::output_frames_max(self)
+    }
+
     fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()> {
         Resampler::set_resample_ratio(self, new_ratio)
     }
@@ -262,6 +552,10 @@ where
     fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()> {
         Resampler::set_resample_ratio_relative(self, rel_ratio)
     }
+
+    fn output_delay(&self) -> usize {
+        Resampler::output_delay(self)
+    }
 }
 
 #[cfg(test)]
@@ -284,4 +578,52 @@ mod tests {
         let waves = vec![vec![0.0f64; frames]; 2];
         resampler.process(&waves).unwrap()
     }
+
+    // A resampler implemented outside the crate, without an `output_delay` method.
+    struct Passthrough;
+
+    impl crate::Resampler<f64> for Passthrough {
+        fn process<V: AsRef<[f64]>>(
+            &mut self,
+            wave_in: &[V],
+        ) -> crate::ResampleResult<Vec<Vec<f64>>> {
+            Ok(wave_in.iter().map(|wave| wave.as_ref().to_vec()).collect())
+        }
+
+        fn nbr_frames_needed(&self) -> usize {
+            16
+        }
+
+        fn output_frames_max(&self) -> usize {
+            16
+        }
+
+        fn set_resample_ratio(&mut self, _new_ratio: f64) -> crate::ResampleResult<()> {
+            Ok(())
+        }
+
+        fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> crate::ResampleResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn default_process_into_buffer() {
+        let mut boxed: Box<dyn VecResampler<f64>> = Box::new(Passthrough);
+        let waves = vec![vec![1.0; 16], Vec::new()];
+        let mut out = vec![vec![0.0; 20], Vec::new()];
+        assert_eq!(boxed.process_into_buffer(&waves, &mut out).unwrap(), 16);
+        assert_eq!(out[0][..16], waves[0][..]);
+        let mut short = vec![vec![0.0; 15], Vec::new()];
+        assert!(boxed.process_into_buffer(&waves, &mut short).is_err());
+        assert!(boxed.process_into_buffer(&waves, &mut out[..1]).is_err());
+    }
+
+    #[test]
+    fn default_output_delay() {
+        let boxed: Box<dyn VecResampler<f64>> = Box::new(Passthrough);
+        assert_eq!(boxed.output_delay(), 0);
+        let result = process_with_boxed(boxed);
+        assert_eq!(result[0].len(), 16);
+    }
 }
@@ -0,0 +1,275 @@
+use crate::error::{ResampleError, ResampleResult};
+use crate::{InterpolationParameters, Resampler, ResamplerFixedOut, Sample, SincFixedOut};
+
+/// A source of a [Mixer], with its own resampler and input buffers.
+struct MixerSource<T> {
+    resampler: SincFixedOut<T>,
+    gain: T,
+    input: Vec<Vec<T>>,
+}
+
+/// A mixer for summing several sources with different sample rates into one output.
+///
+/// Each source has its own [SincFixedOut] resampler that converts it to the output sample rate.
+/// For each output chunk, the mixer asks every source for the number of input frames that its
+/// resampler needs, resamples them, and adds the result to the output with the gain of the source.
+pub struct Mixer<T> {
+    fs_out: usize,
+    chunk_size: usize,
+    nbr_channels: usize,
+    sources: Vec<MixerSource<T>>,
+    resampled: Vec<Vec<T>>,
+}
+
+impl<T> Mixer<T>
+where
+    T: Sample,
+{
+    /// Create a new Mixer
+    ///
+    /// Parameters are:
+    /// - `fs_out`: Output sample rate.
+    /// - `chunk_size`: size of output data in frames.
+    /// - `nbr_channels`: number of channels of the output and of all sources.
+    pub fn new(fs_out: usize, chunk_size: usize, nbr_channels: usize) -> Self {
+        debug!(
+            "Create new Mixer, fs_out: {}, chunk_size: {}, channels: {}",
+            fs_out, chunk_size, nbr_channels
+        );
+        Mixer {
+            fs_out,
+            chunk_size,
+            nbr_channels,
+            sources: Vec::new(),
+            resampled: vec![vec![T::zero(); chunk_size]; nbr_channels],
+        }
+    }
+
+    /// Add a new source, and return its index.
+    ///
+    /// Parameters are:
+    /// - `fs_in`: Sample rate of the source.
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`.
+    /// - `gain`: the gain applied to the source when mixing.
+    pub fn add_source(
+        &mut self,
+        fs_in: usize,
+        parameters: InterpolationParameters,
+        gain: T,
+    ) -> usize {
+        debug!("Add mixer source {}, fs_in: {}", self.sources.len(), fs_in);
+        let resampler = SincFixedOut::new(
+            self.fs_out as f64 / fs_in as f64,
+            parameters,
+            self.chunk_size,
+            self.nbr_channels,
+        );
+        self.sources.push(MixerSource {
+            resampler,
+            gain,
+            input: vec![Vec::new(); self.nbr_channels],
+        });
+        self.sources.len() - 1
+    }
+
+    /// Get the number of sources.
+    pub fn nbr_sources(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Change the gain of a source.
+    pub fn set_gain(&mut self, source: usize, gain: T) {
+        self.sources[source].gain = gain;
+    }
+
+    /// Get the number of frames the next call to "process" will ask a source for.
+    pub fn nbr_frames_needed(&self, source: usize) -> usize {
+        self.sources[source].resampler.nbr_frames_needed()
+    }
+
+    /// Mix a chunk of audio from all sources.
+    ///
+    /// The closure `read_source` is called once for each source, with the index of the source
+    /// and one vector per channel. The vectors have the length given by "nbr_frames_needed"
+    /// for the source, and the closure must fill them with audio at the sample rate of the source.
+    /// A channel of a source can be left out by clearing its vector.
+    /// # Errors
+    ///
+    /// The function returns an error if the closure changed the length of a vector,
+    /// other than by clearing it. All sources are read before any is resampled,
+    /// and on an error none of the resamplers is advanced.
+    pub fn process<F>(&mut self, mut read_source: F) -> ResampleResult<Vec<Vec<T>>>
+    where
+        F: FnMut(usize, &mut [Vec<T>]),
+    {
+        for (idx, source) in self.sources.iter_mut().enumerate() {
+            let needed = source.resampler.nbr_frames_needed();
+            for wave in source.input.iter_mut() {
+                wave.resize(needed, T::zero());
+            }
+            read_source(idx, &mut source.input);
+        }
+        // check all sources before resampling any, so that none advances on an error
+        for source in self.sources.iter() {
+            let needed = source.resampler.nbr_frames_needed();
+            for (chan, wave) in source.input.iter().enumerate() {
+                if !wave.is_empty() && wave.len() != needed {
+                    return Err(ResampleError::WrongNumberOfFrames {
+                        channel: chan,
+                        expected: needed,
+                        actual: wave.len(),
+                    });
+                }
+            }
+        }
+        let mut wave_out = vec![vec![T::zero(); self.chunk_size]; self.nbr_channels];
+        for source in self.sources.iter_mut() {
+            if let Some(err) = ResamplerFixedOut::process(
+                &mut source.resampler,
+                &source.input,
+                &mut self.resampled,
+            ) {
+                return Err(err);
+            }
+            for ((out, resampled), wave_in) in wave_out
+                .iter_mut()
+                .zip(self.resampled.iter())
+                .zip(source.input.iter())
+            {
+                if wave_in.is_empty() {
+                    continue;
+                }
+                for (out_val, val) in out.iter_mut().zip(resampled.iter()) {
+                    *out_val += source.gain * *val;
+                }
+            }
+        }
+        Ok(wave_out)
+    }
+
+    /// Query for the number of frames returned by "process".
+    /// Will always return the chunk_size defined when creating the instance.
+    pub fn nbr_frames_out(&self) -> usize {
+        self.chunk_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InterpolationParameters, InterpolationType, Mixer, WindowFunction};
+
+    fn params() -> InterpolationParameters {
+        InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+
+    #[test]
+    fn mix_sources() {
+        let mut mixer = Mixer::<f64>::new(48000, 256, 2);
+        mixer.add_source(44100, params(), 1.0);
+        mixer.add_source(96000, params(), 2.0);
+        mixer.add_source(22050, params(), 0.5);
+        assert_eq!(mixer.nbr_sources(), 3);
+        let levels = [0.5, 0.25, -0.2];
+        let mut out = Vec::new();
+        for _ in 0..4 {
+            let needed: Vec<usize> = (0..3).map(|src| mixer.nbr_frames_needed(src)).collect();
+            out = mixer
+                .process(|src, waves| {
+                    assert_eq!(waves[0].len(), needed[src]);
+                    for wave in waves.iter_mut() {
+                        wave.iter_mut().for_each(|val| *val = levels[src]);
+                    }
+                })
+                .unwrap();
+            assert_eq!(out[0].len(), 256);
+        }
+        // 0.5 + 2 * 0.25 + 0.5 * -0.2
+        for wave in out.iter() {
+            for val in wave.iter() {
+                assert!((val - 0.9).abs() < 1.0e-3, "got {}", val);
+            }
+        }
+        mixer.set_gain(1, 0.0);
+        for _ in 0..2 {
+            out = mixer
+                .process(|src, waves| {
+                    for wave in waves.iter_mut() {
+                        wave.iter_mut().for_each(|val| *val = levels[src]);
+                    }
+                })
+                .unwrap();
+        }
+        for val in out[0].iter() {
+            assert!((val - 0.4).abs() < 1.0e-3, "got {}", val);
+        }
+    }
+
+    #[test]
+    fn cleared_channel() {
+        let mut mixer = Mixer::<f32>::new(48000, 128, 2);
+        mixer.add_source(44100, params(), 1.0);
+        mixer.add_source(48000, params(), 1.0);
+        let mut out = Vec::new();
+        for _ in 0..4 {
+            out = mixer
+                .process(|src, waves| {
+                    waves[0].iter_mut().for_each(|val| *val = 0.25);
+                    if src == 0 {
+                        waves[1].clear();
+                    } else {
+                        waves[1].iter_mut().for_each(|val| *val = 0.25);
+                    }
+                })
+                .unwrap();
+        }
+        assert!((out[0][100] - 0.5).abs() < 1.0e-3);
+        assert!((out[1][100] - 0.25).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn wrong_length() {
+        let mut mixer = Mixer::<f64>::new(48000, 128, 1);
+        mixer.add_source(44100, params(), 1.0);
+        assert!(mixer.process(|_, waves| waves[0].push(0.0)).is_err());
+        assert!(mixer.process(|_, _| {}).is_ok());
+    }
+
+    #[test]
+    fn wrong_length_keeps_sources_in_step() {
+        let mut mixer = Mixer::<f64>::new(48000, 128, 1);
+        let mut reference = Mixer::<f64>::new(48000, 128, 1);
+        for mix in [&mut mixer, &mut reference].iter_mut() {
+            mix.add_source(44100, params(), 1.0);
+            mix.add_source(32000, params(), 1.0);
+        }
+        let mut counters = [0; 2];
+        let fill = |counters: &mut [usize; 2], src: usize, waves: &mut [Vec<f64>]| {
+            for val in waves[0].iter_mut() {
+                *val = (0.01 * counters[src] as f64).sin();
+                counters[src] += 1;
+            }
+        };
+        let result = mixer.process(|src, waves| {
+            if src == 1 {
+                waves[0].push(0.0);
+            }
+        });
+        assert!(result.is_err());
+        let mut ref_counters = [0; 2];
+        for _ in 0..4 {
+            let out = mixer
+                .process(|src, waves| fill(&mut counters, src, waves))
+                .unwrap();
+            let expected = reference
+                .process(|src, waves| fill(&mut ref_counters, src, waves))
+                .unwrap();
+            assert_eq!(out, expected);
+        }
+    }
+}
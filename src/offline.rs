@@ -0,0 +1,683 @@
+use crate::error::{ResampleError, ResampleResult};
+use crate::{
+    FftFixedIn, InterpolationParameters, InterpolationType, Sample, SincFixedIn, VecResampler,
+    WindowFunction,
+};
+use num_complex::Complex;
+use num_integer as integer;
+use num_traits::Zero;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+#[cfg(not(feature = "parallel"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(feature = "parallel"))]
+use std::thread;
+
+/// The chunk size used when resampling a complete signal.
+const OFFLINE_CHUNK_SIZE: usize = 1024;
+
+/// The number of segments per thread used by [resample_buffer_parallel].
+const PARALLEL_SEGMENTS_PER_THREAD: usize = 4;
+
+/// The length of the zero padding used by [resample_whole_fft], in periods of the transition band.
+const WHOLE_FFT_PADDING: f64 = 16.0;
+
+/// The type of resampler used by [resample_buffer].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    /// Asynchronous resampling with [SincFixedIn].
+    Sinc,
+    /// Synchronous resampling with [FftFixedIn].
+    Fft,
+}
+
+/// Quality presets, trading speed for a sharper filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quality {
+    /// Short filter with linear interpolation.
+    Fast,
+    /// Medium length filter with linear interpolation.
+    Balanced,
+    /// Long filter with cubic interpolation.
+    Best,
+}
+
+impl Quality {
+    /// Get the interpolation parameters of the preset, for use with the sinc resamplers.
+    pub fn interpolation_parameters(&self) -> InterpolationParameters {
+        match self {
+            Quality::Fast => InterpolationParameters {
+                sinc_len: 64,
+                f_cutoff: 0.915_602_1,
+                interpolation: InterpolationType::Linear,
+                oversampling_factor: 1024,
+                window: WindowFunction::Hann2,
+            },
+            Quality::Balanced => InterpolationParameters {
+                sinc_len: 128,
+                f_cutoff: 0.925_914_65,
+                interpolation: InterpolationType::Linear,
+                oversampling_factor: 2048,
+                window: WindowFunction::Blackman2,
+            },
+            Quality::Best => InterpolationParameters {
+                sinc_len: 256,
+                f_cutoff: 0.947_337_15,
+                interpolation: InterpolationType::Cubic,
+                oversampling_factor: 256,
+                window: WindowFunction::BlackmanHarris2,
+            },
+        }
+    }
+
+    /// Get the number of sub chunks of the preset, for use with the FFT resamplers.
+    /// Fewer sub chunks give longer FFTs and a sharper filter.
+    pub fn sub_chunks(&self) -> usize {
+        match self {
+            Quality::Fast => 4,
+            Quality::Balanced => 2,
+            Quality::Best => 1,
+        }
+    }
+}
+
+/// Get a chunk of `len` frames starting at `position`, padded with zeros after the end of the signal.
+fn input_chunk<T>(waves: &[Vec<T>], position: usize, len: usize) -> Vec<Vec<T>>
+where
+    T: Sample,
+{
+    waves
+        .iter()
+        .map(|wave| {
+            let mut chunk =
+                wave[position.min(wave.len())..(position + len).min(wave.len())].to_vec();
+            chunk.resize(len, T::zero());
+            chunk
+        })
+        .collect()
+}
+
+/// Create the FFT resampler used for resampling a complete signal.
+/// It uses an even number of the smallest possible fft chunks,
+/// to make the delay a whole number of frames.
+fn offline_fft_resampler<T>(
+    fs_in: usize,
+    fs_out: usize,
+    quality: Quality,
+    nbr_channels: usize,
+) -> FftFixedIn<T>
+where
+    T: Sample,
+{
+    let sub_chunks = quality.sub_chunks();
+    let min_chunk_in = fs_in / integer::gcd(fs_in, fs_out);
+    let mut fft_chunks = (OFFLINE_CHUNK_SIZE / sub_chunks + min_chunk_in - 1) / min_chunk_in;
+    fft_chunks += fft_chunks % 2;
+    FftFixedIn::<T>::new(
+        fs_in,
+        fs_out,
+        fft_chunks * min_chunk_in * sub_chunks,
+        sub_chunks,
+        nbr_channels,
+    )
+}
+
+/// Resample a complete signal with the given resampler, and return exactly `frames_out` frames.
+/// The input is followed by silence until the resampler has produced enough output,
+/// and the delay of the resampler is removed from the start.
+pub(crate) fn resample_all<T>(
+    resampler: &mut dyn VecResampler<T>,
+    waves: &[Vec<T>],
+    frames_out: usize,
+) -> ResampleResult<Vec<Vec<T>>>
+where
+    T: Sample,
+{
+    let delay = resampler.output_delay();
+    let mut output = vec![Vec::with_capacity(frames_out + delay); waves.len()];
+    let mut position = 0;
+    while output
+        .first()
+        .map(|wave| wave.len())
+        .unwrap_or(frames_out + delay)
+        < frames_out + delay
+    {
+        let needed = resampler.nbr_frames_needed();
+        let chunk = input_chunk(waves, position, needed);
+        position += needed;
+        let resampled = resampler.process(&chunk)?;
+        for (out, wave) in output.iter_mut().zip(resampled.iter()) {
+            out.extend_from_slice(wave);
+        }
+    }
+    for wave in output.iter_mut() {
+        wave.drain(..delay);
+        wave.truncate(frames_out);
+    }
+    Ok(output)
+}
+
+/// Check that all channels have the same length, and return the length.
+fn check_lengths<T>(waves: &[Vec<T>]) -> ResampleResult<usize> {
+    let frames_in = waves.first().map(|wave| wave.len()).unwrap_or(0);
+    for (chan, wave) in waves.iter().enumerate() {
+        if wave.len() != frames_in {
+            return Err(ResampleError::WrongNumberOfFrames {
+                channel: chan,
+                expected: frames_in,
+                actual: wave.len(),
+            });
+        }
+    }
+    Ok(frames_in)
+}
+
+/// Resample a complete signal in one call.
+///
+/// The input has one vector per channel, and all channels must have the same length.
+/// The output has exactly `round(len * fs_out / fs_in)` frames per channel.
+/// The delay of the resampler is removed, so that the output lines up with the input.
+///
+/// Parameters are:
+/// - `waves`: The complete input signal.
+/// - `fs_in`: Input sample rate.
+/// - `fs_out`: Output sample rate.
+/// - `engine`: The type of resampler to use.
+/// - `quality`: The quality preset.
+/// # Errors
+///
+/// The function returns an error if the channels have different lengths.
+pub fn resample_buffer<T>(
+    waves: &[Vec<T>],
+    fs_in: usize,
+    fs_out: usize,
+    engine: Engine,
+    quality: Quality,
+) -> ResampleResult<Vec<Vec<T>>>
+where
+    T: Sample,
+{
+    let frames_in = check_lengths(waves)?;
+    let frames_out = (frames_in as f64 * fs_out as f64 / fs_in as f64).round() as usize;
+    debug!(
+        "Resample buffer, {} Hz to {} Hz, {} frames to {} frames, {:?}, {:?}",
+        fs_in, fs_out, frames_in, frames_out, engine, quality
+    );
+    let mut resampler: Box<dyn VecResampler<T>> = match engine {
+        Engine::Sinc => {
+            let mut resampler = SincFixedIn::<T>::new(
+                fs_out as f64 / fs_in as f64,
+                quality.interpolation_parameters(),
+                OFFLINE_CHUNK_SIZE,
+                waves.len(),
+            );
+            resampler.align_output_delay();
+            Box::new(resampler)
+        }
+        Engine::Fft => Box::new(offline_fft_resampler::<T>(
+            fs_in,
+            fs_out,
+            quality,
+            waves.len(),
+        )),
+    };
+    resample_all(resampler.as_mut(), waves, frames_out)
+}
+
+/// Resample the chunks `first` to `end` of a complete signal with a new resampler.
+/// The chunk before `first` is processed first and its output discarded, so that the overlap
+/// of the resampler is the same as when the whole signal is processed in one go.
+fn resample_segment<T>(
+    waves: &[Vec<T>],
+    fs_in: usize,
+    fs_out: usize,
+    quality: Quality,
+    first: usize,
+    end: usize,
+) -> ResampleResult<Vec<Vec<T>>>
+where
+    T: Sample,
+{
+    let mut resampler = offline_fft_resampler::<T>(fs_in, fs_out, quality, waves.len());
+    let chunk_size = resampler.nbr_frames_needed();
+    if first > 0 {
+        resampler.process(&input_chunk(waves, (first - 1) * chunk_size, chunk_size))?;
+    }
+    let mut output = vec![Vec::new(); waves.len()];
+    for chunk in first..end {
+        let resampled = resampler.process(&input_chunk(waves, chunk * chunk_size, chunk_size))?;
+        for (out, wave) in output.iter_mut().zip(resampled.iter()) {
+            out.extend_from_slice(wave);
+        }
+    }
+    Ok(output)
+}
+
+/// The result of resampling one segment of a signal.
+type SegmentResult<T> = ResampleResult<Vec<Vec<T>>>;
+
+/// Get the number of threads to use when none is given.
+#[cfg(feature = "parallel")]
+fn default_threads() -> usize {
+    rayon::current_num_threads()
+}
+
+/// Get the number of threads to use when none is given.
+#[cfg(not(feature = "parallel"))]
+fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+}
+
+/// Resample all segments on a rayon thread pool, and return the results in order.
+/// The current pool is used if it has the requested number of threads,
+/// otherwise a new pool is created for this call.
+#[cfg(feature = "parallel")]
+fn resample_segments<T, F>(
+    nbr_segments: usize,
+    nbr_threads: usize,
+    resample: F,
+) -> Vec<SegmentResult<T>>
+where
+    T: Send,
+    F: Fn(usize) -> SegmentResult<T> + Sync,
+{
+    let run = || (0..nbr_segments).into_par_iter().map(&resample).collect();
+    if nbr_threads == rayon::current_num_threads() {
+        return run();
+    }
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(nbr_threads)
+        .build()
+    {
+        Ok(pool) => pool.install(run),
+        Err(err) => {
+            debug!("Can't create a thread pool, using the current one: {}", err);
+            run()
+        }
+    }
+}
+
+/// Resample all segments on scoped threads that take the segments one at a time,
+/// and return the results in order.
+/// The calling thread also takes segments, and it resamples all of them
+/// when only one thread is requested or when no threads can be started.
+#[cfg(not(feature = "parallel"))]
+fn resample_segments<T, F>(
+    nbr_segments: usize,
+    nbr_threads: usize,
+    resample: F,
+) -> Vec<SegmentResult<T>>
+where
+    T: Send,
+    F: Fn(usize) -> SegmentResult<T> + Sync,
+{
+    let next_segment = AtomicUsize::new(0);
+    let work = || {
+        let mut done = Vec::new();
+        loop {
+            let segment = next_segment.fetch_add(1, Ordering::Relaxed);
+            if segment >= nbr_segments {
+                break;
+            }
+            done.push((segment, resample(segment)));
+        }
+        done
+    };
+    let mut segments: Vec<(usize, SegmentResult<T>)> = thread::scope(|scope| {
+        let mut workers = Vec::new();
+        for _ in 1..nbr_threads.min(nbr_segments) {
+            match thread::Builder::new().spawn_scoped(scope, work) {
+                Ok(worker) => workers.push(worker),
+                Err(err) => {
+                    debug!("Can't start a thread, continuing with fewer: {}", err);
+                    break;
+                }
+            }
+        }
+        let mut done = work();
+        for worker in workers {
+            done.extend(worker.join().unwrap());
+        }
+        done
+    });
+    segments.sort_by_key(|(segment, _)| *segment);
+    segments
+        .into_iter()
+        .map(|(_, resampled)| resampled)
+        .collect()
+}
+
+/// Resample a complete signal using several threads.
+///
+/// The signal is split into segments that are resampled in parallel, each with its own
+/// FFT resampler. Every segment starts one chunk early, to give the resampler the same
+/// overlap as when processing the whole signal, and this chunk is discarded.
+/// The joined output is bit-identical to that of [resample_buffer] with [Engine::Fft].
+/// Only the FFT engine is supported. This isn't possible with the sinc resampler,
+/// since its position accumulates rounding errors over the whole signal.
+///
+/// With the `parallel` feature, the segments are resampled on a [rayon](https://crates.io/crates/rayon) thread pool.
+/// Otherwise a set of scoped threads is started for each call.
+/// If no threads can be started, the segments are resampled on the calling thread.
+///
+/// The output has exactly `round(len * fs_out / fs_in)` frames per channel,
+/// and the delay of the resampler is removed.
+///
+/// Parameters are:
+/// - `waves`: The complete input signal.
+/// - `fs_in`: Input sample rate.
+/// - `fs_out`: Output sample rate.
+/// - `quality`: The quality preset.
+/// - `nbr_threads`: Number of threads to use, or 0 to use the available parallelism.
+///   With the `parallel` feature, 0 means all threads of the current rayon pool.
+///
+/// # Errors
+///
+/// The function returns an error if the channels have different lengths.
+pub fn resample_buffer_parallel<T>(
+    waves: &[Vec<T>],
+    fs_in: usize,
+    fs_out: usize,
+    quality: Quality,
+    nbr_threads: usize,
+) -> ResampleResult<Vec<Vec<T>>>
+where
+    T: Sample,
+{
+    let frames_in = check_lengths(waves)?;
+    let frames_out = (frames_in as f64 * fs_out as f64 / fs_in as f64).round() as usize;
+    if waves.is_empty() {
+        return Ok(Vec::new());
+    }
+    let nbr_threads = if nbr_threads == 0 {
+        default_threads()
+    } else {
+        nbr_threads
+    };
+    let resampler = offline_fft_resampler::<T>(fs_in, fs_out, quality, waves.len());
+    let delay = resampler.output_delay();
+    let chunk_size_out = resampler.nbr_frames_needed() * fs_out / fs_in;
+    let nbr_chunks = (frames_out + delay + chunk_size_out - 1) / chunk_size_out;
+    // Use a few segments per thread, to keep all threads busy until the end.
+    let nbr_segments = nbr_chunks.min(PARALLEL_SEGMENTS_PER_THREAD * nbr_threads);
+    let segment_len = (nbr_chunks + nbr_segments - 1) / nbr_segments;
+    debug!(
+        "Resample buffer in parallel, {} Hz to {} Hz, {} frames to {} frames, {} segments of {} chunks, {} threads",
+        fs_in, fs_out, frames_in, frames_out, nbr_segments, segment_len, nbr_threads
+    );
+
+    let segments = resample_segments(nbr_segments, nbr_threads, |segment| {
+        let first = segment * segment_len;
+        let end = (first + segment_len).min(nbr_chunks);
+        trace!("Resample segment {}, chunks {} to {}", segment, first, end);
+        resample_segment(waves, fs_in, fs_out, quality, first, end)
+    });
+
+    let mut output = vec![Vec::with_capacity(nbr_chunks * chunk_size_out); waves.len()];
+    for resampled in segments {
+        for (out, wave) in output.iter_mut().zip(resampled?.iter()) {
+            out.extend_from_slice(wave);
+        }
+    }
+    for wave in output.iter_mut() {
+        wave.drain(..delay);
+        wave.truncate(frames_out);
+    }
+    Ok(output)
+}
+
+/// Get the gain of the taper at frequency `freq`, for a band that ends at `edge`
+/// and a raised cosine transition that starts at `start`.
+fn taper_gain(freq: f64, start: f64, edge: f64) -> f64 {
+    if freq < start {
+        1.0
+    } else if freq >= edge && edge > start {
+        0.0
+    } else if edge > start {
+        0.5 * (1.0 + (std::f64::consts::PI * (freq - start) / (edge - start)).cos())
+    } else {
+        // A bin exactly at the edge of a brick wall filter gets half the gain.
+        0.5
+    }
+}
+
+/// Resample a complete signal with a single FFT per channel.
+///
+/// Each channel is padded with zeros and transformed as a whole. The spectrum is then truncated
+/// or extended with zeros to the length needed for the new sample rate, multiplied with a taper
+/// towards the lower of the two Nyquist frequencies, and inverse transformed.
+/// Unlike the chunked [FftFixedIn], this has zero phase, no delay and no block artefacts.
+/// The output has exactly `round(len * fs_out / fs_in)` frames per channel.
+///
+/// The taper is a raised cosine, and its width is given as a fraction of the band that is kept.
+/// A value of 0.0 gives a brick wall filter, while 1.0 tapers off over the whole band.
+/// A narrow taper gives a wide passband, but a long ringing at the edges of the passband.
+/// Values around 0.05 are a reasonable starting point for audio.
+///
+/// Parameters are:
+/// - `waves`: The complete input signal.
+/// - `fs_in`: Input sample rate.
+/// - `fs_out`: Output sample rate.
+/// - `taper`: Width of the taper, as a fraction of the band, in the range 0.0 to 1.0.
+///
+/// # Errors
+///
+/// The function returns an error if the channels have different lengths,
+/// or if the taper is outside the range 0 to 1.
+pub fn resample_whole_fft<T>(
+    waves: &[Vec<T>],
+    fs_in: usize,
+    fs_out: usize,
+    taper: f64,
+) -> ResampleResult<Vec<Vec<T>>>
+where
+    T: Sample,
+{
+    if !(0.0..=1.0).contains(&taper) {
+        return Err(ResampleError::BadTaper { taper });
+    }
+    let frames_in = check_lengths(waves)?;
+    let frames_out = (frames_in as f64 * fs_out as f64 / fs_in as f64).round() as usize;
+
+    // Pad with zeros so that the ringing of the filter doesn't wrap around,
+    // and round up to a length that gives a whole number of output frames.
+    let gcd = integer::gcd(fs_in, fs_out);
+    let min_chunk_in = fs_in / gcd;
+    let min_chunk_out = fs_out / gcd;
+    let padding = (WHOLE_FFT_PADDING * fs_in as f64 / (taper.max(0.01) * fs_in.min(fs_out) as f64))
+        .ceil() as usize;
+    let fft_len_in = (frames_in + padding + min_chunk_in - 1) / min_chunk_in * min_chunk_in;
+    let fft_len_out = fft_len_in / min_chunk_in * min_chunk_out;
+    debug!(
+        "Resample whole signal by FFT, {} Hz to {} Hz, {} frames to {} frames, fft lengths {} and {}, taper {}",
+        fs_in, fs_out, frames_in, frames_out, fft_len_in, fft_len_out, taper
+    );
+
+    let mut planner = RealFftPlanner::<T>::new();
+    let fft = planner.plan_fft_forward(fft_len_in);
+    let ifft = planner.plan_fft_inverse(fft_len_out);
+    let mut wave_in = fft.make_input_vec();
+    let mut spectrum_in = fft.make_output_vec();
+    let mut spectrum_out = ifft.make_input_vec();
+    let mut wave_out = ifft.make_output_vec();
+
+    // The gains include the normalization of the transforms.
+    let edge = fft_len_in.min(fft_len_out) as f64 / 2.0;
+    let start = (1.0 - taper) * edge;
+    let gains: Vec<T> = (0..(fft_len_in.min(fft_len_out) / 2 + 1))
+        .map(|bin| T::coerce(taper_gain(bin as f64, start, edge) / fft_len_in as f64))
+        .collect();
+
+    let mut output = Vec::with_capacity(waves.len());
+    for wave in waves.iter() {
+        wave_in[..frames_in].copy_from_slice(wave);
+        for val in wave_in[frames_in..].iter_mut() {
+            *val = T::zero();
+        }
+        fft.process(&mut wave_in, &mut spectrum_in).unwrap();
+        for val in spectrum_out.iter_mut() {
+            *val = Complex::zero();
+        }
+        for ((out, spec), gain) in spectrum_out
+            .iter_mut()
+            .zip(spectrum_in.iter())
+            .zip(gains.iter())
+        {
+            *out = spec * gain;
+        }
+        // The Nyquist bin of an even length real signal must be real.
+        if fft_len_out % 2 == 0 {
+            spectrum_out[fft_len_out / 2].im = T::zero();
+        }
+        ifft.process(&mut spectrum_out, &mut wave_out).unwrap();
+        output.push(wave_out[..frames_out].to_vec());
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{resample_buffer, resample_buffer_parallel, resample_whole_fft, Engine, Quality};
+
+    fn sine(len: usize, freq: f64, fs: usize) -> Vec<f64> {
+        (0..len)
+            .map(|n| (2.0 * std::f64::consts::PI * freq * n as f64 / fs as f64).sin())
+            .collect()
+    }
+
+    fn check_sine(engine: Engine, quality: Quality, fs_in: usize, fs_out: usize) {
+        let waves = vec![sine(5000, 500.0, fs_in), vec![0.0; 5000]];
+        let output = resample_buffer(&waves, fs_in, fs_out, engine, quality).unwrap();
+        let expected_len = (5000.0 * fs_out as f64 / fs_in as f64).round() as usize;
+        assert_eq!(output[0].len(), expected_len);
+        assert_eq!(output[1].len(), expected_len);
+        let expected = sine(expected_len, 500.0, fs_out);
+        // skip the ends, where the filter sees the edges of the signal
+        for (val, exp) in output[0]
+            .iter()
+            .zip(expected.iter())
+            .take(expected_len - 200)
+            .skip(200)
+        {
+            assert!((val - exp).abs() < 1.0e-3, "got {}, expected {}", val, exp);
+        }
+        assert!(output[1].iter().all(|val| val.abs() < 1.0e-9));
+    }
+
+    #[test]
+    fn sinc_up_and_down() {
+        check_sine(Engine::Sinc, Quality::Best, 44100, 48000);
+        check_sine(Engine::Sinc, Quality::Fast, 48000, 32000);
+    }
+
+    #[test]
+    fn fft_up_and_down() {
+        check_sine(Engine::Fft, Quality::Balanced, 44100, 96000);
+        check_sine(Engine::Fft, Quality::Best, 48000, 44100);
+    }
+
+    #[test]
+    fn short_and_empty() {
+        let output = resample_buffer(
+            &[vec![0.5f32; 3]],
+            44100,
+            48000,
+            Engine::Sinc,
+            Quality::Fast,
+        )
+        .unwrap();
+        assert_eq!(output[0].len(), 3);
+        let output =
+            resample_buffer::<f32>(&[Vec::new()], 44100, 48000, Engine::Fft, Quality::Fast)
+                .unwrap();
+        assert!(output[0].is_empty());
+        assert!(resample_buffer(
+            &[vec![0.0f64; 3], vec![0.0; 4]],
+            44100,
+            48000,
+            Engine::Fft,
+            Quality::Fast
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn whole_fft_up_and_down() {
+        for (fs_in, fs_out) in [(44100, 48000), (48000, 32000), (44100, 44100)].iter() {
+            let waves = vec![sine(5001, 500.0, *fs_in), vec![0.0; 5001]];
+            let output = resample_whole_fft(&waves, *fs_in, *fs_out, 0.1).unwrap();
+            let expected_len = (5001.0 * *fs_out as f64 / *fs_in as f64).round() as usize;
+            assert_eq!(output[0].len(), expected_len);
+            let expected = sine(expected_len, 500.0, *fs_out);
+            // there is no delay and no block artefacts, only ringing from the edges of the signal
+            for (val, exp) in output[0]
+                .iter()
+                .zip(expected.iter())
+                .take(expected_len - 300)
+                .skip(300)
+            {
+                assert!((val - exp).abs() < 1.0e-6, "got {}, expected {}", val, exp);
+            }
+            assert!(output[1].iter().all(|val| val.abs() < 1.0e-12));
+        }
+    }
+
+    #[test]
+    fn whole_fft_removes_aliases() {
+        // 20 kHz is above the new Nyquist frequency of 16 kHz
+        let waves = vec![sine(4800, 20000.0, 48000)];
+        let output = resample_whole_fft(&waves, 48000, 32000, 0.05).unwrap();
+        for val in output[0].iter().take(3000).skip(200) {
+            assert!(val.abs() < 1.0e-3, "got {}", val);
+        }
+    }
+
+    #[test]
+    fn whole_fft_bad_taper() {
+        assert!(resample_whole_fft(&[vec![0.0f32; 10]], 44100, 48000, 1.5).is_err());
+        assert!(resample_whole_fft(&[vec![0.0f32; 10]], 44100, 48000, -0.1).is_err());
+        let output = resample_whole_fft::<f64>(&[Vec::new()], 44100, 48000, 0.0).unwrap();
+        assert!(output[0].is_empty());
+    }
+
+    #[test]
+    fn parallel_is_bit_identical() {
+        let waves = vec![sine(100_000, 500.0, 44100), sine(100_000, 1234.5, 44100)];
+        for (fs_out, quality) in [(48000, Quality::Balanced), (22050, Quality::Best)].iter() {
+            let serial = resample_buffer(&waves, 44100, *fs_out, Engine::Fft, *quality).unwrap();
+            for nbr_threads in [1, 3, 8, 0].iter() {
+                let parallel =
+                    resample_buffer_parallel(&waves, 44100, *fs_out, *quality, *nbr_threads)
+                        .unwrap();
+                assert_eq!(parallel, serial);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_short_and_empty() {
+        let waves = vec![vec![0.5f32; 100]];
+        let serial = resample_buffer(&waves, 44100, 48000, Engine::Fft, Quality::Fast).unwrap();
+        let parallel = resample_buffer_parallel(&waves, 44100, 48000, Quality::Fast, 4).unwrap();
+        assert_eq!(parallel, serial);
+        let output =
+            resample_buffer_parallel::<f64>(&[Vec::new()], 44100, 48000, Quality::Fast, 4).unwrap();
+        assert!(output[0].is_empty());
+        assert!(
+            resample_buffer_parallel::<f64>(&[], 44100, 48000, Quality::Fast, 4)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(resample_buffer_parallel(
+            &[vec![0.0f64; 3], vec![0.0; 4]],
+            44100,
+            48000,
+            Quality::Fast,
+            4
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use crate::error::{ResampleError, ResampleResult};
+use crate::windows::WindowFunction;
+use crate::{HalfbandDecimator, HalfbandInterpolator, Resampler, Sample};
+
+/// A helper for running a nonlinear process, such as a saturator or clipper, at a higher sample rate.
+///
+/// Each chunk of audio is upsampled by the oversampling factor using a series of halfband
+/// interpolators. The oversampled audio is handed to a closure that may modify it in place,
+/// and is then decimated back to the original sample rate by a matching series of halfband decimators.
+/// The number of frames is the same for input and output.
+pub struct Oversampler<T> {
+    nbr_channels: usize,
+    chunk_size: usize,
+    interpolators: Vec<HalfbandInterpolator<T>>,
+    decimators: Vec<HalfbandDecimator<T>>,
+    buffers: Vec<Vec<Vec<T>>>,
+}
+
+impl<T> Oversampler<T>
+where
+    T: Sample,
+{
+    /// Create a new Oversampler
+    ///
+    /// Parameters are:
+    /// - `factor`: oversampling factor, must be a power of two, for example 2, 4 or 8.
+    /// - `nbr_coeffs`: number of nonzero coefficients on each side of the center tap
+    ///   of the halfband filters. The latency is a whole number of frames when
+    ///   `4 * nbr_coeffs` is divisible by `factor`.
+    /// - `window`: window function used for the halfband filters.
+    /// - `chunk_size`: size of input and output data in frames.
+    /// - `nbr_channels`: number of channels in input/output.
+    pub fn new(
+        factor: usize,
+        nbr_coeffs: usize,
+        window: WindowFunction,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new Oversampler, factor: {}, nbr_coeffs: {}, chunk_size: {}, channels: {}",
+            factor, nbr_coeffs, chunk_size, nbr_channels
+        );
+        assert!(
+            factor >= 2 && factor.is_power_of_two(),
+            "Oversampling factor must be a power of two, got {}",
+            factor
+        );
+        let nbr_stages = factor.trailing_zeros() as usize;
+        let interpolators = (0..nbr_stages)
+            .map(|stage| {
+                HalfbandInterpolator::new(nbr_coeffs, window, chunk_size << stage, nbr_channels)
+            })
+            .collect();
+        let decimators = (0..nbr_stages)
+            .rev()
+            .map(|stage| {
+                HalfbandDecimator::new(nbr_coeffs, window, chunk_size << (stage + 1), nbr_channels)
+            })
+            .collect();
+        let buffers = (0..=nbr_stages)
+            .map(|stage| vec![vec![T::zero(); chunk_size << stage]; nbr_channels])
+            .collect();
+        Oversampler {
+            nbr_channels,
+            chunk_size,
+            interpolators,
+            decimators,
+            buffers,
+        }
+    }
+
+    /// Upsample a chunk of audio, process it with `process`, and downsample the result.
+    ///
+    /// The closure gets the oversampled audio for all channels, and may modify it in place.
+    /// It must not change the lengths of the vectors.
+    /// If the waveform for a channel is empty, this channel will be ignored. It is passed to
+    /// the closure as an empty vector, and produces a corresponding empty output waveform.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input data is not equal
+    /// to the number of channels and chunk size defined when creating the instance,
+    /// or if the closure changed the length of a channel.
+    pub fn process<V, F>(&mut self, wave_in: &[V], mut process: F) -> ResampleResult<Vec<Vec<T>>>
+    where
+        V: AsRef<[T]>,
+        F: FnMut(&mut [Vec<T>]),
+    {
+        if wave_in.len() != self.nbr_channels {
+            return Err(ResampleError::WrongNumberOfChannels {
+                expected: self.nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        let mut used_channels = Vec::new();
+        for (chan, wave) in wave_in.iter().enumerate() {
+            let wave = wave.as_ref();
+            if !wave.is_empty() {
+                used_channels.push(chan);
+                if wave.len() != self.chunk_size {
+                    return Err(ResampleError::WrongNumberOfFrames {
+                        channel: chan,
+                        expected: self.chunk_size,
+                        actual: wave.len(),
+                    });
+                }
+            }
+        }
+
+        for &chan in used_channels.iter() {
+            self.buffers[0][chan].copy_from_slice(wave_in[chan].as_ref());
+        }
+        for (stage, interpolator) in self.interpolators.iter_mut().enumerate() {
+            let (lower, upper) = self.buffers.split_at_mut(stage + 1);
+            for &chan in used_channels.iter() {
+                interpolator.process_channel(chan, &lower[stage][chan], &mut upper[0][chan]);
+            }
+        }
+
+        let oversampled_len = self.chunk_size << self.interpolators.len();
+        let top = self.buffers.last_mut().unwrap();
+        for (chan, wave) in top.iter_mut().enumerate() {
+            if !used_channels.contains(&chan) {
+                wave.clear();
+            }
+        }
+        process(top);
+        for (chan, wave) in top.iter_mut().enumerate() {
+            if used_channels.contains(&chan) {
+                if wave.len() != oversampled_len {
+                    let actual = wave.len();
+                    wave.resize(oversampled_len, T::zero());
+                    return Err(ResampleError::WrongNumberOfOutputFrames {
+                        channel: chan,
+                        expected: oversampled_len,
+                        actual,
+                    });
+                }
+            } else {
+                wave.resize(oversampled_len, T::zero());
+            }
+        }
+
+        let nbr_stages = self.decimators.len();
+        for (stage, decimator) in self.decimators.iter_mut().enumerate() {
+            let (lower, upper) = self.buffers.split_at_mut(nbr_stages - stage);
+            let output = &mut lower[nbr_stages - stage - 1];
+            for &chan in used_channels.iter() {
+                decimator.process_channel(chan, &upper[0][chan], &mut output[chan]);
+            }
+        }
+
+        let mut wave_out = vec![Vec::new(); self.nbr_channels];
+        for &chan in used_channels.iter() {
+            wave_out[chan] = self.buffers[0][chan].clone();
+        }
+        Ok(wave_out)
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    /// Will always return the chunk_size defined when creating the instance.
+    pub fn nbr_frames_needed(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Get the oversampling factor.
+    pub fn factor(&self) -> usize {
+        1 << self.interpolators.len()
+    }
+
+    /// Get the total delay of the upsampling and downsampling filters, in frames at the
+    /// original sample rate. This is rounded to the nearest frame.
+    pub fn output_delay(&self) -> usize {
+        // delay in frames at the oversampled rate
+        let mut delay = 0;
+        for interpolator in self.interpolators.iter() {
+            delay = 2 * delay + interpolator.output_delay();
+        }
+        let factor = self.factor();
+        let mut scale = factor;
+        for decimator in self.decimators.iter() {
+            scale /= 2;
+            delay += decimator.output_delay() * factor / scale;
+        }
+        (delay + factor / 2) / factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Oversampler, WindowFunction};
+
+    fn sine(start: usize, len: usize, omega: f64) -> Vec<f64> {
+        (start..start + len)
+            .map(|n| (omega * n as f64).sin())
+            .collect()
+    }
+
+    fn check_passthrough(factor: usize) {
+        let mut oversampler =
+            Oversampler::<f64>::new(factor, 24, WindowFunction::BlackmanHarris2, 256, 1);
+        let delay = oversampler.output_delay();
+        let mut output = Vec::new();
+        for chunk in 0..8 {
+            let wave = sine(256 * chunk, 256, 0.1);
+            let out = oversampler
+                .process(&[wave], |waves| {
+                    assert_eq!(waves[0].len(), 256 * factor);
+                })
+                .unwrap();
+            assert_eq!(out[0].len(), 256);
+            output.extend_from_slice(&out[0]);
+        }
+        let expected = sine(0, output.len() - delay, 0.1);
+        for (val, exp) in output.iter().skip(delay).zip(expected.iter()).skip(200) {
+            assert!((val - exp).abs() < 1.0e-4, "got {}, expected {}", val, exp);
+        }
+    }
+
+    #[test]
+    fn passthrough_2x() {
+        check_passthrough(2);
+    }
+
+    #[test]
+    fn passthrough_4x() {
+        check_passthrough(4);
+    }
+
+    #[test]
+    fn passthrough_8x() {
+        check_passthrough(8);
+    }
+
+    #[test]
+    fn delays() {
+        let oversampler = Oversampler::<f32>::new(2, 24, WindowFunction::BlackmanHarris2, 64, 1);
+        assert_eq!(oversampler.output_delay(), 48);
+        let oversampler = Oversampler::<f32>::new(4, 24, WindowFunction::BlackmanHarris2, 64, 1);
+        assert_eq!(oversampler.output_delay(), 72);
+        let oversampler = Oversampler::<f32>::new(8, 24, WindowFunction::BlackmanHarris2, 64, 1);
+        assert_eq!(oversampler.output_delay(), 84);
+    }
+
+    #[test]
+    fn gain_in_closure() {
+        let mut oversampler =
+            Oversampler::<f64>::new(4, 16, WindowFunction::BlackmanHarris2, 128, 2);
+        let waves = vec![vec![1.0; 128], Vec::new()];
+        let mut out = Vec::new();
+        for _ in 0..4 {
+            out = oversampler
+                .process(&waves, |waves| {
+                    assert!(waves[1].is_empty());
+                    waves[0].iter_mut().for_each(|v| *v *= 0.5);
+                })
+                .unwrap();
+        }
+        assert!(out[1].is_empty());
+        for val in out[0].iter() {
+            assert!((val - 0.5).abs() < 1.0e-6);
+        }
+    }
+
+    #[test]
+    fn closure_changes_length() {
+        let mut oversampler =
+            Oversampler::<f64>::new(2, 16, WindowFunction::BlackmanHarris2, 128, 1);
+        let waves = vec![vec![0.0; 128]];
+        let result = oversampler.process(&waves, |waves| waves[0].push(0.0));
+        assert!(result.is_err());
+        // the buffers are restored and the next call works
+        assert!(oversampler.process(&waves, |_| {}).is_ok());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::error::{ResampleError, ResampleResult};
+use crate::{Sample, VecResampler};
+
+/// A wrapper that lets a resampler pull its input on demand, for filling output buffers of any length.
+///
+/// This suits audio device callbacks, where the number of output frames is given
+/// and the input should be fetched as needed, similar to the callback API of libsamplerate.
+/// Output that is left over from the last call to the resampler is stored
+/// and used first in the next call to "read".
+pub struct PullResampler<T> {
+    resampler: Box<dyn VecResampler<T>>,
+    nbr_channels: usize,
+    input: Vec<Vec<T>>,
+    output: Vec<Vec<T>>,
+}
+
+impl<T> PullResampler<T>
+where
+    T: Sample,
+{
+    /// Create a new PullResampler
+    ///
+    /// Parameters are:
+    /// - `resampler`: The resampler to use, of any type.
+    /// - `nbr_channels`: number of channels in input/output, must match the resampler.
+    pub fn new(resampler: Box<dyn VecResampler<T>>, nbr_channels: usize) -> Self {
+        debug!("Create new PullResampler, channels: {}", nbr_channels);
+        PullResampler {
+            resampler,
+            nbr_channels,
+            input: vec![Vec::new(); nbr_channels],
+            output: vec![Vec::new(); nbr_channels],
+        }
+    }
+
+    /// Fill an output buffer with resampled audio.
+    ///
+    /// The output buffer uses the same layout as the output of [crate::Resampler::process],
+    /// and all channels must have the same length, which may be anything.
+    /// The closure `read_input` is called as many times as needed, with one vector per channel.
+    /// The vectors have the length given by "nbr_frames_needed" of the resampler,
+    /// and the closure must fill them with input audio without changing their lengths.
+    /// # Errors
+    ///
+    /// The function returns an error if the number of channels of the output buffer is not equal
+    /// to the number defined when creating the instance, if the channels have different lengths,
+    /// or if the closure changed the length of a vector.
+    pub fn read<W, F>(&mut self, wave_out: &mut [W], mut read_input: F) -> ResampleResult<()>
+    where
+        W: AsMut<[T]>,
+        F: FnMut(&mut [Vec<T>]),
+    {
+        if wave_out.len() != self.nbr_channels {
+            return Err(ResampleError::WrongNumberOfChannels {
+                expected: self.nbr_channels,
+                actual: wave_out.len(),
+            });
+        }
+        let frames = wave_out
+            .first_mut()
+            .map(|wave| wave.as_mut().len())
+            .unwrap_or(0);
+        for (chan, wave) in wave_out.iter_mut().enumerate() {
+            if wave.as_mut().len() != frames {
+                return Err(ResampleError::WrongNumberOfFrames {
+                    channel: chan,
+                    expected: frames,
+                    actual: wave.as_mut().len(),
+                });
+            }
+        }
+        while self.output.first().map(|wave| wave.len()).unwrap_or(frames) < frames {
+            let needed = self.resampler.nbr_frames_needed();
+            for wave in self.input.iter_mut() {
+                wave.resize(needed, T::zero());
+            }
+            read_input(&mut self.input);
+            for (chan, wave) in self.input.iter().enumerate() {
+                if wave.len() != needed {
+                    return Err(ResampleError::WrongNumberOfFrames {
+                        channel: chan,
+                        expected: needed,
+                        actual: wave.len(),
+                    });
+                }
+            }
+            let resampled = self.resampler.process(&self.input)?;
+            trace!(
+                "Pulled {} frames of input, got {} frames of output",
+                needed,
+                resampled.first().map(|wave| wave.len()).unwrap_or(0)
+            );
+            for (buffer, wave) in self.output.iter_mut().zip(resampled.iter()) {
+                buffer.extend_from_slice(wave);
+            }
+        }
+        for (wave, buffer) in wave_out.iter_mut().zip(self.output.iter_mut()) {
+            wave.as_mut().copy_from_slice(&buffer[..frames]);
+            buffer.drain(..frames);
+        }
+        Ok(())
+    }
+
+    /// Get the number of resampled frames that are stored, and will be used first by "read".
+    pub fn frames_available(&self) -> usize {
+        self.output.first().map(|wave| wave.len()).unwrap_or(0)
+    }
+
+    /// Update the resample ratio of the resampler, see [crate::Resampler::set_resample_ratio].
+    /// The stored output frames are not affected.
+    pub fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()> {
+        self.resampler.set_resample_ratio(new_ratio)
+    }
+
+    /// Update the resample ratio of the resampler relative to the original one,
+    /// see [crate::Resampler::set_resample_ratio_relative].
+    /// The stored output frames are not affected.
+    pub fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+        self.resampler.set_resample_ratio_relative(rel_ratio)
+    }
+
+    /// Get the delay of the output relative to the input, in output frames.
+    pub fn output_delay(&self) -> usize {
+        self.resampler.output_delay()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        FftFixedIn, InterpolationParameters, InterpolationType, PullResampler, SincFixedOut,
+        WindowFunction,
+    };
+
+    #[test]
+    fn pull_any_length() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let resampler = SincFixedOut::<f64>::new(48000.0 / 44100.0, params, 256, 2);
+        let mut puller = PullResampler::new(Box::new(resampler), 2);
+        let mut frames_in = 0;
+        let mut frames_out = 0;
+        for len in [1, 100, 1000, 37, 512].iter() {
+            let mut out = vec![vec![0.0; *len]; 2];
+            puller
+                .read(&mut out, |waves| {
+                    frames_in += waves[0].len();
+                    for wave in waves.iter_mut() {
+                        wave.iter_mut().for_each(|val| *val = 0.5);
+                    }
+                })
+                .unwrap();
+            frames_out += len;
+            assert_eq!(out[0].len(), *len);
+        }
+        // only as much input as needed has been pulled
+        let produced = frames_out + puller.frames_available();
+        assert!(produced - frames_out < 256);
+        assert!((produced as f64 - frames_in as f64 * 48000.0 / 44100.0).abs() < 256.0);
+        let mut out = vec![vec![0.0; 100]; 2];
+        puller
+            .read(&mut out, |waves| {
+                for wave in waves.iter_mut() {
+                    wave.iter_mut().for_each(|val| *val = 0.5);
+                }
+            })
+            .unwrap();
+        for val in out[1].iter() {
+            assert!((val - 0.5).abs() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 1);
+        let mut puller = PullResampler::new(Box::new(resampler), 1);
+        let mut out = vec![vec![0.0; 10]; 2];
+        assert!(puller.read(&mut out, |_| {}).is_err());
+        let mut out = vec![vec![0.0; 10]];
+        assert!(puller.read(&mut out, |waves| waves[0].clear()).is_err());
+        assert!(puller.read(&mut out, |_| {}).is_ok());
+        // the resampler gives 640 frames per chunk
+        assert_eq!(puller.frames_available(), 640 - 10);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{AvxSample, NeonSample, SseSample};
+use crate::{Avx512Sample, AvxSample, NeonSample, Simd128Sample, SseSample};
 
 /// The trait governing a single sample.
 ///
@@ -22,8 +22,10 @@ where
         + std::ops::SubAssign
         + std::ops::AddAssign
         + AvxSample
+        + Avx512Sample
         + SseSample
-        + NeonSample,
+        + NeonSample
+        + Simd128Sample,
 {
     const PI: Self;
 
@@ -98,13 +100,13 @@ impl CoerceFrom<f64> for f32 {
 
 impl CoerceFrom<f64> for f64 {
     fn coerce_from(value: f64) -> Self {
-        value as f64
+        value
     }
 }
 
 impl CoerceFrom<f32> for f32 {
     fn coerce_from(value: f32) -> Self {
-        value as f32
+        value
     }
 }
 
@@ -0,0 +1,382 @@
+use crate::error::{ResampleError, ResampleResult};
+use crate::{Sample, VecResampler};
+
+/// A fixed size first-in first-out buffer for one channel.
+/// The stored frames are `data[start..end]`.
+struct Fifo<T> {
+    data: Vec<T>,
+    start: usize,
+    end: usize,
+}
+
+impl<T> Fifo<T>
+where
+    T: Sample,
+{
+    fn new(capacity: usize) -> Self {
+        Fifo {
+            data: vec![T::zero(); capacity],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    fn stored(&self) -> usize {
+        self.end - self.start
+    }
+
+    fn free(&self) -> usize {
+        self.data.len() - self.stored()
+    }
+
+    /// Get the stored frames.
+    fn frames(&self) -> &[T] {
+        &self.data[self.start..self.end]
+    }
+
+    /// Store frames after the ones already stored. There must be room for them.
+    fn push(&mut self, frames: &[T]) {
+        if self.start > 0 {
+            self.data.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+        self.data[self.end..self.end + frames.len()].copy_from_slice(frames);
+        self.end += frames.len();
+    }
+}
+
+/// Check that a buffer has the expected number of channels, and that all channels have the same length.
+/// Returns the number of frames.
+fn check_frames<V: AsRef<[T]>, T>(waves: &[V], nbr_channels: usize) -> ResampleResult<usize> {
+    if waves.len() != nbr_channels {
+        return Err(ResampleError::WrongNumberOfChannels {
+            expected: nbr_channels,
+            actual: waves.len(),
+        });
+    }
+    let frames = waves.first().map(|wave| wave.as_ref().len()).unwrap_or(0);
+    for (chan, wave) in waves.iter().enumerate() {
+        if wave.as_ref().len() != frames {
+            return Err(ResampleError::WrongNumberOfFrames {
+                channel: chan,
+                expected: frames,
+                actual: wave.as_ref().len(),
+            });
+        }
+    }
+    Ok(frames)
+}
+
+/// Same as `check_frames`, for output buffers.
+fn check_frames_mut<W: AsMut<[T]>, T>(
+    waves: &mut [W],
+    nbr_channels: usize,
+) -> ResampleResult<usize> {
+    if waves.len() != nbr_channels {
+        return Err(ResampleError::WrongNumberOfOutputChannels {
+            expected: nbr_channels,
+            actual: waves.len(),
+        });
+    }
+    let frames = waves
+        .first_mut()
+        .map(|wave| wave.as_mut().len())
+        .unwrap_or(0);
+    for (chan, wave) in waves.iter_mut().enumerate() {
+        if wave.as_mut().len() != frames {
+            return Err(ResampleError::WrongNumberOfOutputFrames {
+                channel: chan,
+                expected: frames,
+                actual: wave.as_mut().len(),
+            });
+        }
+    }
+    Ok(frames)
+}
+
+/// A streaming resampler that accepts and returns any number of frames per call.
+///
+/// The input is stored in an internal buffer until there is enough for the next chunk
+/// of the wrapped resampler, and the output is stored until it is read.
+/// All buffers are allocated when the instance is created. Writing and reading do not allocate,
+/// as long as the resampler doesn't allocate in "process_into_buffer", like [crate::SincFixedOut].
+pub struct StreamResampler<T> {
+    resampler: Box<dyn VecResampler<T>>,
+    nbr_channels: usize,
+    input: Vec<Fifo<T>>,
+    output: Vec<Fifo<T>>,
+    chunk_in: Vec<Vec<T>>,
+    chunk_out: Vec<Vec<T>>,
+}
+
+impl<T> StreamResampler<T>
+where
+    T: Sample,
+{
+    /// Create a new StreamResampler
+    ///
+    /// Parameters are:
+    /// - `resampler`: The resampler to use, of any type.
+    /// - `nbr_channels`: number of channels in input/output, must match the resampler.
+    pub fn new(resampler: Box<dyn VecResampler<T>>, nbr_channels: usize) -> Self {
+        debug!("Create new StreamResampler, channels: {}", nbr_channels);
+        let frames_in = resampler.input_frames_max();
+        let frames_out = resampler.output_frames_max();
+        StreamResampler {
+            resampler,
+            nbr_channels,
+            input: (0..nbr_channels)
+                .map(|_| Fifo::new(2 * frames_in))
+                .collect(),
+            output: (0..nbr_channels)
+                .map(|_| Fifo::new(2 * frames_out))
+                .collect(),
+            chunk_in: vec![Vec::with_capacity(frames_in); nbr_channels],
+            chunk_out: vec![vec![T::zero(); frames_out]; nbr_channels],
+        }
+    }
+
+    /// Write input frames. The input may have any number of frames, but all channels
+    /// must have the same length. The frames are stored as long as there is room in the
+    /// input buffer, and the number of stored frames is returned.
+    /// If this is less than the length of the input, the rest should be written again
+    /// after reading some output.
+    /// # Errors
+    ///
+    /// The function returns an error if the number of channels is not equal to the number
+    /// defined when creating the instance, or if the channels have different lengths.
+    pub fn write<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<usize> {
+        let frames = check_frames(wave_in, self.nbr_channels)?;
+        let accepted = frames.min(self.input_frames_free());
+        for (fifo, wave) in self.input.iter_mut().zip(wave_in.iter()) {
+            fifo.push(&wave.as_ref()[..accepted]);
+        }
+        trace!("Stored {} of {} input frames", accepted, frames);
+        Ok(accepted)
+    }
+
+    /// Read as much output as is available, up to the length of the output buffer.
+    /// All channels of the output buffer must have the same length, which may be anything.
+    /// Returns the number of frames written to the output buffer.
+    /// # Errors
+    ///
+    /// The function returns an error if the number of channels is not equal to the number
+    /// defined when creating the instance, or if the channels have different lengths.
+    pub fn read<W: AsMut<[T]>>(&mut self, wave_out: &mut [W]) -> ResampleResult<usize> {
+        let frames = check_frames_mut(wave_out, self.nbr_channels)?;
+        let mut written = 0;
+        loop {
+            let count = self.output_frames_available().min(frames - written);
+            for (fifo, wave) in self.output.iter_mut().zip(wave_out.iter_mut()) {
+                wave.as_mut()[written..written + count].copy_from_slice(&fifo.frames()[..count]);
+                fifo.start += count;
+            }
+            written += count;
+            if written == frames || !self.resample_chunk()? {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    /// Fill the whole output buffer, if there is enough input for it.
+    /// Returns false and leaves the output buffer untouched if there is not.
+    /// # Errors
+    ///
+    /// The function returns an error if the number of channels is not equal to the number
+    /// defined when creating the instance, if the channels have different lengths,
+    /// or if they are longer than the largest output of one chunk of the resampler.
+    pub fn read_block<W: AsMut<[T]>>(&mut self, wave_out: &mut [W]) -> ResampleResult<bool> {
+        let frames = check_frames_mut(wave_out, self.nbr_channels)?;
+        let max_frames = self.resampler.output_frames_max();
+        if frames > max_frames {
+            return Err(ResampleError::WrongNumberOfOutputFrames {
+                channel: 0,
+                expected: max_frames,
+                actual: frames,
+            });
+        }
+        while self.output_frames_available() < frames {
+            if !self.resample_chunk()? {
+                return Ok(false);
+            }
+        }
+        Ok(self.read(wave_out)? == frames)
+    }
+
+    /// Get the number of input frames that can be written before the input buffer is full.
+    pub fn input_frames_free(&self) -> usize {
+        self.input.first().map(|fifo| fifo.free()).unwrap_or(0)
+    }
+
+    /// Get the number of output frames that are ready to be read without resampling more input.
+    pub fn output_frames_available(&self) -> usize {
+        self.output.first().map(|fifo| fifo.stored()).unwrap_or(0)
+    }
+
+    /// Update the resample ratio, see [crate::Resampler::set_resample_ratio].
+    pub fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()> {
+        self.resampler.set_resample_ratio(new_ratio)
+    }
+
+    /// Update the resample ratio relative to the original one,
+    /// see [crate::Resampler::set_resample_ratio_relative].
+    pub fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+        self.resampler.set_resample_ratio_relative(rel_ratio)
+    }
+
+    /// Get the delay of the output relative to the input, in output frames.
+    pub fn output_delay(&self) -> usize {
+        self.resampler.output_delay()
+    }
+
+    /// Resample one chunk from the input buffer to the output buffer, if there is enough
+    /// input and room for the output. Returns true if a chunk was resampled.
+    /// Without channels there is nothing to resample.
+    fn resample_chunk(&mut self) -> ResampleResult<bool> {
+        let needed = self.resampler.nbr_frames_needed();
+        let stored = self.input.first().map(|fifo| fifo.stored()).unwrap_or(0);
+        let free = self.output.first().map(|fifo| fifo.free()).unwrap_or(0);
+        if self.nbr_channels == 0 || stored < needed || free < self.resampler.output_frames_max() {
+            return Ok(false);
+        }
+        for (chunk, fifo) in self.chunk_in.iter_mut().zip(self.input.iter()) {
+            chunk.clear();
+            chunk.extend_from_slice(&fifo.frames()[..needed]);
+        }
+        let frames = self
+            .resampler
+            .process_into_buffer(&self.chunk_in, &mut self.chunk_out)?;
+        for fifo in self.input.iter_mut() {
+            fifo.start += needed;
+        }
+        for (fifo, chunk) in self.output.iter_mut().zip(self.chunk_out.iter()) {
+            fifo.push(&chunk[..frames]);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        FftFixedIn, InterpolationParameters, InterpolationType, Resampler, SincFixedOut,
+        StreamResampler, WindowFunction,
+    };
+
+    fn params() -> InterpolationParameters {
+        InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+
+    fn sinc_stream<T: crate::Sample>(
+        ratio: f64,
+        chunk_size: usize,
+        channels: usize,
+    ) -> StreamResampler<T> {
+        let resampler = SincFixedOut::<T>::new(ratio, params(), chunk_size, channels);
+        StreamResampler::new(Box::new(resampler), channels)
+    }
+
+    /// Resample the input by writing and reading blocks of varying lengths.
+    fn stream_varying(stream: &mut StreamResampler<f64>, input: &[f64]) -> Vec<f64> {
+        let mut output = Vec::new();
+        let mut pos = 0;
+        let mut out = vec![vec![0.0; 1000]];
+        for (n, len) in [1, 17, 900, 2000, 333, 5000].iter().cycle().enumerate() {
+            if pos == input.len() {
+                break;
+            }
+            let end = (pos + len).min(input.len());
+            pos += stream.write(&[&input[pos..end]]).unwrap();
+            let out_len = [1000, 1, 77][n % 3];
+            let written = stream.read(&mut [&mut out[0][..out_len]]).unwrap();
+            output.extend_from_slice(&out[0][..written]);
+        }
+        loop {
+            let written = stream.read(&mut out).unwrap();
+            if written == 0 {
+                break;
+            }
+            output.extend_from_slice(&out[0][..written]);
+        }
+        output
+    }
+
+    #[test]
+    fn same_as_fixed_out() {
+        let ratio = 48000.0 / 44100.0;
+        let input: Vec<f64> = (0..20000).map(|n| (0.013 * n as f64).sin()).collect();
+
+        let mut resampler = SincFixedOut::<f64>::new(ratio, params(), 256, 1);
+        let mut expected = Vec::new();
+        let mut pos = 0;
+        while pos + resampler.nbr_frames_needed() <= input.len() {
+            let needed = resampler.nbr_frames_needed();
+            let out = resampler.process(&[&input[pos..pos + needed]]).unwrap();
+            expected.extend_from_slice(&out[0]);
+            pos += needed;
+        }
+
+        let mut stream = sinc_stream::<f64>(ratio, 256, 1);
+        let output = stream_varying(&mut stream, &input);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn same_as_fft_fixed_in() {
+        let input: Vec<f64> = (0..20000).map(|n| (0.013 * n as f64).sin()).collect();
+
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 1);
+        let expected: Vec<f64> = input
+            .chunks_exact(1024)
+            .flat_map(|chunk| resampler.process(&[chunk]).unwrap().remove(0))
+            .collect();
+
+        let resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 1);
+        let mut stream = StreamResampler::new(Box::new(resampler), 1);
+        let output = stream_varying(&mut stream, &input);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn blocks() {
+        let mut stream = sinc_stream::<f32>(0.5, 128, 2);
+        let mut block = vec![vec![0.0; 128]; 2];
+        assert!(!stream.read_block(&mut block).unwrap());
+        let free = stream.input_frames_free();
+        assert_eq!(stream.write(&vec![vec![0.25; free + 10]; 2]).unwrap(), free);
+        let mut nbr_blocks = 0;
+        while stream.read_block(&mut block).unwrap() {
+            nbr_blocks += 1;
+        }
+        assert!(nbr_blocks >= free / 256 - 1);
+        assert!((block[1][100] - 0.25).abs() < 1.0e-3);
+        assert!(stream.read_block(&mut vec![vec![0.0; 129]; 2]).is_err());
+    }
+
+    #[test]
+    fn wrong_channels() {
+        let mut stream = sinc_stream::<f64>(1.5, 128, 2);
+        assert!(stream.write(&[vec![0.0; 10]]).is_err());
+        assert!(stream.write(&[vec![0.0; 10], vec![0.0; 9]]).is_err());
+        assert!(stream.read(&mut [vec![0.0; 10]]).is_err());
+    }
+
+    #[test]
+    fn no_channels() {
+        let mut stream = sinc_stream::<f64>(1.5, 128, 0);
+        assert_eq!(stream.input_frames_free(), 0);
+        assert_eq!(stream.output_frames_available(), 0);
+        let mut empty: [Vec<f64>; 0] = [];
+        assert_eq!(stream.write(&empty).unwrap(), 0);
+        assert_eq!(stream.read(&mut empty).unwrap(), 0);
+        assert!(stream.read_block(&mut empty).unwrap());
+    }
+}
@@ -7,9 +7,12 @@ use std::sync::Arc;
 
 use crate::error::{ResampleError, ResampleResult};
 use crate::{Resampler, Sample};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
 
 /// A helper for resampling a single chunk of data.
+#[derive(Clone)]
 struct FftResampler<T> {
     fft_size_in: usize,
     fft_size_out: usize,
@@ -38,7 +41,7 @@ pub struct FftFixedIn<T> {
     overlaps: Vec<Vec<T>>,
     input_buffers: Vec<Vec<T>>,
     saved_frames: usize,
-    resampler: FftResampler<T>,
+    resamplers: Vec<FftResampler<T>>,
 }
 
 /// A synchronous resampler that needs a varying number of audio frames for input
@@ -56,7 +59,7 @@ pub struct FftFixedOut<T> {
     output_buffers: Vec<Vec<T>>,
     saved_frames: usize,
     frames_needed: usize,
-    resampler: FftResampler<T>,
+    resamplers: Vec<FftResampler<T>>,
 }
 
 /// A synchronous resampler that accepts a fixed number of audio frames for input
@@ -71,7 +74,7 @@ pub struct FftFixedInOut<T> {
     chunk_size_out: usize,
     fft_size_in: usize,
     overlaps: Vec<Vec<T>>,
-    resampler: FftResampler<T>,
+    resamplers: Vec<FftResampler<T>>,
 }
 
 impl<T> FftResampler<T>
@@ -123,6 +126,13 @@ where
         }
     }
 
+    /// Get the delay in output frames. The filter is centered at
+    /// sample `fft_size_in / 2` of the input chunk.
+    fn output_delay(&self) -> usize {
+        ((self.fft_size_in / 2 * self.fft_size_out) as f64 / self.fft_size_in as f64).round()
+            as usize
+    }
+
     /// Resample a small chunk
     fn resample_unit(&mut self, wave_in: &[T], wave_out: &mut [T], overlap: &mut [T]) {
         // Copy to input buffer and clear padding area
@@ -173,6 +183,92 @@ where
         }
         overlap.copy_from_slice(&self.output_buf[self.fft_size_out..]);
     }
+
+    /// Resample all the small chunks of one channel.
+    fn resample_channel(&mut self, wave_in: &[T], wave_out: &mut [T], overlap: &mut [T]) {
+        for (in_chunk, out_chunk) in wave_in
+            .chunks(self.fft_size_in)
+            .zip(wave_out.chunks_mut(self.fft_size_out))
+        {
+            self.resample_unit(in_chunk, out_chunk, overlap);
+        }
+    }
+}
+
+/// Get the number of groups of channels that are processed separately, each with
+/// its own FftResampler. Without the `parallel` feature, all channels are processed as one group.
+fn nbr_channel_groups(nbr_channels: usize) -> usize {
+    #[cfg(feature = "parallel")]
+    let max_groups = rayon::current_num_threads();
+    #[cfg(not(feature = "parallel"))]
+    let max_groups = 1;
+    nbr_channels.clamp(1, max_groups)
+}
+
+/// Create one FftResampler for each group of channels.
+fn make_resamplers<T>(
+    fft_size_in: usize,
+    fft_size_out: usize,
+    nbr_channels: usize,
+) -> Vec<FftResampler<T>>
+where
+    T: Sample,
+{
+    vec![FftResampler::<T>::new(fft_size_in, fft_size_out); nbr_channel_groups(nbr_channels)]
+}
+
+/// Resample the channels of one group. Channels with empty input are skipped.
+fn resample_group<T>(
+    resampler: &mut FftResampler<T>,
+    wave_in: &[&[T]],
+    wave_out: &mut [Vec<T>],
+    overlaps: &mut [Vec<T>],
+    out_offset: usize,
+) where
+    T: Sample,
+{
+    for ((input, output), overlap) in wave_in
+        .iter()
+        .zip(wave_out.iter_mut())
+        .zip(overlaps.iter_mut())
+    {
+        if !input.is_empty() {
+            resampler.resample_channel(input, &mut output[out_offset..], overlap);
+        }
+    }
+}
+
+/// Resample all channels, writing the output from `out_offset`. The channels are split
+/// in one group per resampler, and with the `parallel` feature the groups are processed
+/// on separate threads.
+fn resample_channels<T>(
+    resamplers: &mut [FftResampler<T>],
+    wave_in: &[&[T]],
+    wave_out: &mut [Vec<T>],
+    overlaps: &mut [Vec<T>],
+    out_offset: usize,
+) where
+    T: Sample,
+{
+    let group_size = ((wave_in.len() + resamplers.len() - 1) / resamplers.len()).max(1);
+    #[cfg(not(feature = "parallel"))]
+    resamplers
+        .iter_mut()
+        .zip(wave_in.chunks(group_size))
+        .zip(wave_out.chunks_mut(group_size))
+        .zip(overlaps.chunks_mut(group_size))
+        .for_each(|(((resampler, inputs), outputs), overlaps)| {
+            resample_group(resampler, inputs, outputs, overlaps, out_offset)
+        });
+    #[cfg(feature = "parallel")]
+    resamplers
+        .par_iter_mut()
+        .zip(wave_in.par_chunks(group_size))
+        .zip(wave_out.par_chunks_mut(group_size))
+        .zip(overlaps.par_chunks_mut(group_size))
+        .for_each(|(((resampler, inputs), outputs), overlaps)| {
+            resample_group(resampler, inputs, outputs, overlaps, out_offset)
+        });
 }
 
 impl<T> FftFixedInOut<T>
@@ -199,7 +295,7 @@ where
         let fft_size_out = fft_chunks * fs_out / gcd;
         let fft_size_in = fft_chunks * fs_in / gcd;
 
-        let resampler = FftResampler::<T>::new(fft_size_in, fft_size_out);
+        let resamplers = make_resamplers::<T>(fft_size_in, fft_size_out, nbr_channels);
 
         let overlaps: Vec<Vec<T>> = vec![vec![T::zero(); fft_size_out]; nbr_channels];
 
@@ -209,7 +305,7 @@ where
             chunk_size_out: fft_size_out,
             fft_size_in,
             overlaps,
-            resampler,
+            resamplers,
         }
     }
 }
@@ -223,6 +319,11 @@ where
         self.fft_size_in
     }
 
+    /// Get the number of frames returned by "process", which is fixed.
+    fn output_frames_max(&self) -> usize {
+        self.chunk_size_out
+    }
+
     /// Resample a chunk of audio. The input and output lengths are fixed.
     /// If the waveform for a channel is empty, this channel will be ignored and produce a
     /// corresponding empty output waveform.
@@ -256,13 +357,14 @@ where
             wave_out[*chan] = vec![T::zero(); self.chunk_size_out];
         }
 
-        for n in used_channels.iter() {
-            self.resampler.resample_unit(
-                wave_in[*n].as_ref(),
-                &mut wave_out[*n],
-                &mut self.overlaps[*n],
-            )
-        }
+        let wave_in: Vec<&[T]> = wave_in.iter().map(|wave| wave.as_ref()).collect();
+        resample_channels(
+            &mut self.resamplers,
+            &wave_in,
+            &mut wave_out,
+            &mut self.overlaps,
+            0,
+        );
         Ok(wave_out)
     }
 
@@ -277,6 +379,11 @@ where
     fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> ResampleResult<()> {
         Err(ResampleError::SyncNotAdjustable)
     }
+
+    /// Get the delay of the output relative to the input, in output frames.
+    fn output_delay(&self) -> usize {
+        self.resamplers[0].output_delay()
+    }
 }
 
 impl<T> FftFixedOut<T>
@@ -305,7 +412,7 @@ where
         let fft_size_out = fft_chunks * fs_out / gcd;
         let fft_size_in = fft_chunks * fs_in / gcd;
 
-        let resampler = FftResampler::<T>::new(fft_size_in, fft_size_out);
+        let resamplers = make_resamplers::<T>(fft_size_in, fft_size_out, nbr_channels);
 
         debug!(
             "Create new FftFixedOut, fs_in: {}, fs_out: {} chunk_size_in: {}, channels: {}, fft_size_in: {}, fft_size_out: {}",
@@ -329,9 +436,16 @@ where
             output_buffers,
             saved_frames,
             frames_needed,
-            resampler,
+            resamplers,
         }
     }
+
+    /// Get the number of output frames after the end of the input that depend on the input,
+    /// counted from the end of the input without the delay. The fft chunk holding the end
+    /// of the input can end one fft chunk later, and its overlap reaches another fft chunk past that.
+    pub(crate) fn tail_len(&self) -> usize {
+        2 * self.fft_size_out
+    }
 }
 
 impl<T> Resampler<T> for FftFixedOut<T>
@@ -343,6 +457,17 @@ where
         self.frames_needed
     }
 
+    /// Get the largest number of frames that "nbr_frames_needed" can return,
+    /// when no output frames are saved from the previous call.
+    fn input_frames_max(&self) -> usize {
+        (self.chunk_size_out as f32 / self.fft_size_out as f32).ceil() as usize * self.fft_size_in
+    }
+
+    /// Get the number of frames returned by "process", which is fixed.
+    fn output_frames_max(&self) -> usize {
+        self.chunk_size_out
+    }
+
     /// Resample a chunk of audio. The required input length is provided by
     /// the "nbr_frames_needed" function, and the output length is fixed.
     /// If the waveform for a channel is empty, this channel will be ignored and produce a
@@ -379,16 +504,14 @@ where
             wave_out[*chan] = self.output_buffers[*chan].clone();
         }
 
-        for n in used_channels.iter() {
-            for (in_chunk, out_chunk) in wave_in[*n]
-                .as_ref()
-                .chunks(self.fft_size_in)
-                .zip(wave_out[*n][self.saved_frames..].chunks_mut(self.fft_size_out))
-            {
-                self.resampler
-                    .resample_unit(in_chunk, out_chunk, &mut self.overlaps[*n]);
-            }
-        }
+        let wave_in: Vec<&[T]> = wave_in.iter().map(|wave| wave.as_ref()).collect();
+        resample_channels(
+            &mut self.resamplers,
+            &wave_in,
+            &mut wave_out,
+            &mut self.overlaps,
+            self.saved_frames,
+        );
         let processed_frames =
             self.saved_frames + self.fft_size_out * (self.frames_needed / self.fft_size_in);
 
@@ -405,11 +528,7 @@ where
             wave_out[*n].truncate(self.chunk_size_out);
         }
         //calculate number of needed frames from next round
-        let frames_needed_out = if self.chunk_size_out > self.saved_frames {
-            self.chunk_size_out - self.saved_frames
-        } else {
-            0
-        };
+        let frames_needed_out = self.chunk_size_out.saturating_sub(self.saved_frames);
         let chunks_needed = (frames_needed_out as f32 / self.fft_size_out as f32).ceil() as usize;
         self.frames_needed = chunks_needed * self.fft_size_in;
         Ok(wave_out)
@@ -426,6 +545,11 @@ where
     fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> ResampleResult<()> {
         Err(ResampleError::SyncNotAdjustable)
     }
+
+    /// Get the delay of the output relative to the input, in output frames.
+    fn output_delay(&self) -> usize {
+        self.resamplers[0].output_delay()
+    }
 }
 
 impl<T> FftFixedIn<T>
@@ -454,7 +578,7 @@ where
         let fft_size_out = fft_chunks * fs_out / gcd;
         let fft_size_in = fft_chunks * fs_in / gcd;
 
-        let resampler = FftResampler::<T>::new(fft_size_in, fft_size_out);
+        let resamplers = make_resamplers::<T>(fft_size_in, fft_size_out, nbr_channels);
         debug!(
             "Create new FftFixedOut, fs_in: {}, fs_out: {} chunk_size_in: {}, channels: {}, fft_size_in: {}, fft_size_out: {}",
             fs_in, fs_out, chunk_size_in, nbr_channels, fft_size_in, fft_size_out
@@ -474,7 +598,7 @@ where
             overlaps,
             input_buffers,
             saved_frames,
-            resampler,
+            resamplers,
         }
     }
 }
@@ -488,6 +612,12 @@ where
         self.chunk_size_in
     }
 
+    /// Get the largest number of frames that one call to "process" can return.
+    /// The saved input frames are always fewer than one fft chunk.
+    fn output_frames_max(&self) -> usize {
+        (self.chunk_size_in as f32 / self.fft_size_in as f32).ceil() as usize * self.fft_size_out
+    }
+
     /// Resample a chunk of audio. The required input length is provided by
     /// the "nbr_frames_needed" function, and the output length is fixed.
     /// If the waveform for a channel is empty, this channel will be ignored and produce a
@@ -552,16 +682,17 @@ where
         for chan in used_channels.iter() {
             wave_out[*chan] = vec![T::zero(); nbr_chunks_ready * self.fft_size_out];
         }
-        for n in used_channels.iter() {
-            for (in_chunk, out_chunk) in input_temp[*n]
-                .chunks(self.fft_size_in)
-                .take(nbr_chunks_ready)
-                .zip(wave_out[*n].chunks_mut(self.fft_size_out))
-            {
-                self.resampler
-                    .resample_unit(in_chunk, out_chunk, &mut self.overlaps[*n]);
-            }
-        }
+        let wave_in: Vec<&[T]> = input_temp
+            .iter()
+            .map(|wave| &wave[..wave.len().min(nbr_chunks_ready * self.fft_size_in)])
+            .collect();
+        resample_channels(
+            &mut self.resamplers,
+            &wave_in,
+            &mut wave_out,
+            &mut self.overlaps,
+            0,
+        );
 
         // save extra frames for next round
         let frames_in_used = nbr_chunks_ready * self.fft_size_in;
@@ -594,6 +725,11 @@ where
     fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> ResampleResult<()> {
         Err(ResampleError::SyncNotAdjustable)
     }
+
+    /// Get the delay of the output relative to the input, in output frames.
+    fn output_delay(&self) -> usize {
+        self.resamplers[0].output_delay()
+    }
 }
 
 #[cfg(test)]
@@ -617,7 +753,7 @@ mod tests {
         let mut overlap = vec![0.0; 1000];
         resampler.resample_unit(&wave_in, &mut wave_out, &mut overlap);
         let vecsum = wave_out.iter().sum::<f64>();
-        let maxval = wave_out.iter().cloned().fold(0. / 0., f64::max);
+        let maxval = wave_out.iter().cloned().fold(f64::NAN, f64::max);
         assert!((vecsum - 4.0 * 1000.0 / 147.0).abs() < 1.0e-6);
         assert!((maxval - 1.0).abs() < 0.1);
     }
@@ -680,6 +816,17 @@ mod tests {
         assert!(out[1].is_empty());
     }
 
+    #[test]
+    fn output_delay() {
+        // the filter is centered in the fft chunk
+        let resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        assert_eq!(resampler.output_delay(), 320);
+        let resampler = FftFixedOut::<f64>::new(44100, 192000, 1024, 2, 2);
+        assert_eq!(resampler.output_delay(), 318);
+        let resampler = FftFixedInOut::<f64>::new(44100, 48000, 1024, 2);
+        assert_eq!(resampler.output_delay(), 559);
+    }
+
     #[test]
     fn make_resampler_fi() {
         let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
@@ -747,4 +894,21 @@ mod tests {
         assert_eq!(out.len(), 2);
         assert_eq!(out[0].len(), 1024);
     }
+
+    #[test]
+    fn many_channels_fi() {
+        // each channel gives the same result as when resampled on its own,
+        // also when the channels are split into groups
+        let nbr_channels = 17;
+        let waves: Vec<Vec<f64>> = (0..nbr_channels)
+            .map(|chan| (0..1024).map(|n| ((n * (chan + 1)) as f64).sin()).collect())
+            .collect();
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, nbr_channels);
+        let out = resampler.process(&waves).unwrap();
+        for (chan, wave) in waves.iter().enumerate() {
+            let mut single = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 1);
+            let expected = single.process(&[wave]).unwrap();
+            assert_eq!(out[chan], expected[0]);
+        }
+    }
 }
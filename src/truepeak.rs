@@ -0,0 +1,268 @@
+use crate::asynchro::{ScalarInterpolator, SincInterpolator};
+use crate::error::{ResampleError, ResampleResult};
+use crate::Sample;
+
+/// The polyphase interpolation filter for 4x oversampling given in ITU-R BS.1770-4, Annex 2.
+/// Each row is one phase of 12 taps.
+const BS1770_PHASES: [[f64; 12]; 4] = [
+    [
+        0.0017089843750,
+        0.0109863281250,
+        -0.0196533203125,
+        0.0332031250000,
+        -0.0594482421875,
+        0.1373291015625,
+        0.9721679687500,
+        -0.1022949218750,
+        0.0476074218750,
+        -0.0266113281250,
+        0.0148925781250,
+        -0.0083007812500,
+    ],
+    [
+        -0.0291748046875,
+        0.0292968750000,
+        -0.0517578125000,
+        0.0891113281250,
+        -0.1665039062500,
+        0.4650878906250,
+        0.7797851562500,
+        -0.2003173828125,
+        0.1015625000000,
+        -0.0582275390625,
+        0.0330810546875,
+        -0.0189208984375,
+    ],
+    [
+        -0.0189208984375,
+        0.0330810546875,
+        -0.0582275390625,
+        0.1015625000000,
+        -0.2003173828125,
+        0.7797851562500,
+        0.4650878906250,
+        -0.1665039062500,
+        0.0891113281250,
+        -0.0517578125000,
+        0.0292968750000,
+        -0.0291748046875,
+    ],
+    [
+        -0.0083007812500,
+        0.0148925781250,
+        -0.0266113281250,
+        0.0476074218750,
+        -0.1022949218750,
+        0.9721679687500,
+        0.1373291015625,
+        -0.0594482421875,
+        0.0332031250000,
+        -0.0196533203125,
+        0.0109863281250,
+        0.0017089843750,
+    ],
+];
+
+/// Make the BS.1770 filter phases, padded with zeros to a length of 16 to suit the interpolators.
+fn make_bs1770_sincs<T>() -> Vec<Vec<T>>
+where
+    T: Sample,
+{
+    BS1770_PHASES
+        .iter()
+        .map(|phase| {
+            let mut sinc = vec![T::zero(); 2];
+            sinc.extend(phase.iter().map(|c| T::coerce(*c)));
+            sinc.extend_from_slice(&[T::zero(); 2]);
+            sinc
+        })
+        .collect()
+}
+
+/// A true-peak meter, measuring the inter-sample peak level of each channel.
+///
+/// The audio is oversampled by sinc interpolation, and the meter keeps the highest absolute
+/// value of the oversampled signal. By default the 4x oversampling filter given in ITU-R BS.1770
+/// is used. Any other interpolator can be used instead, for example a [ScalarInterpolator]
+/// with a longer filter or a higher oversampling factor for a more accurate reading.
+pub struct TruePeakMeter<T> {
+    nbr_channels: usize,
+    interpolator: Box<dyn SincInterpolator<T>>,
+    history: Vec<Vec<T>>,
+    buffer: Vec<T>,
+    peaks: Vec<T>,
+}
+
+impl<T> TruePeakMeter<T>
+where
+    T: Sample + PartialOrd,
+{
+    /// Create a new TruePeakMeter using the 4x oversampling filter of ITU-R BS.1770.
+    ///
+    /// Parameters are:
+    /// - `nbr_channels`: number of channels to measure.
+    pub fn new(nbr_channels: usize) -> Self {
+        let interpolator = Box::new(ScalarInterpolator::new_with_sincs(make_bs1770_sincs()));
+        Self::new_with_interpolator(interpolator, nbr_channels)
+    }
+
+    /// Create a new TruePeakMeter using a custom interpolator.
+    /// The oversampling factor is given by the number of sincs of the interpolator.
+    ///
+    /// Parameters are:
+    /// - `interpolator`: The interpolator to use.
+    /// - `nbr_channels`: number of channels to measure.
+    pub fn new_with_interpolator(
+        interpolator: Box<dyn SincInterpolator<T>>,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new TruePeakMeter, oversampling: {}, filter length: {}, channels: {}",
+            interpolator.nbr_sincs(),
+            interpolator.len(),
+            nbr_channels
+        );
+        let history = vec![vec![T::zero(); interpolator.len()]; nbr_channels];
+        TruePeakMeter {
+            nbr_channels,
+            interpolator,
+            history,
+            buffer: Vec::new(),
+            peaks: vec![T::zero(); nbr_channels],
+        }
+    }
+
+    /// Measure a chunk of audio.
+    ///
+    /// The input uses the same layout as [crate::Resampler::process], but the chunks may have any length.
+    /// If the waveform for a channel is empty, this channel will be ignored.
+    /// # Errors
+    ///
+    /// The function returns an error if the number of channels of the input data is not equal
+    /// to the number of channels defined when creating the instance.
+    pub fn process<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<()> {
+        if wave_in.len() != self.nbr_channels {
+            return Err(ResampleError::WrongNumberOfChannels {
+                expected: self.nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        let sinc_len = self.interpolator.len();
+        for (chan, wave) in wave_in.iter().enumerate() {
+            let wave = wave.as_ref();
+            if wave.is_empty() {
+                continue;
+            }
+            // The interpolators need one sample more than the filter length,
+            // pad with a zero to include the last sample of the chunk.
+            self.buffer.clear();
+            self.buffer.extend_from_slice(&self.history[chan]);
+            self.buffer.extend_from_slice(wave);
+            self.buffer.push(T::zero());
+            let mut peak = self.peaks[chan];
+            for index in 0..wave.len() {
+                for subindex in 0..self.interpolator.nbr_sincs() {
+                    let value = self
+                        .interpolator
+                        .get_sinc_interpolated(&self.buffer, index + 1, subindex)
+                        .abs();
+                    if value > peak {
+                        peak = value;
+                    }
+                }
+            }
+            self.peaks[chan] = peak;
+            self.history[chan].copy_from_slice(&self.buffer[wave.len()..wave.len() + sinc_len]);
+        }
+        Ok(())
+    }
+
+    /// Get the highest true-peak value of each channel since creation or the last reset,
+    /// as a linear value where 1.0 is full scale.
+    pub fn true_peak(&self) -> Vec<T> {
+        self.peaks.clone()
+    }
+
+    /// Get the highest true-peak value of each channel since creation or the last reset, in dBTP.
+    /// Channels that have only received silence give negative infinity.
+    pub fn true_peak_dbtp(&self) -> Vec<T>
+    where
+        T: num_traits::Float,
+    {
+        self.peaks
+            .iter()
+            .map(|peak| T::coerce(20.0) * peak.log10())
+            .collect()
+    }
+
+    /// Reset the peak values and clear the filter history.
+    pub fn reset(&mut self) {
+        self.peaks.iter_mut().for_each(|peak| *peak = T::zero());
+        self.history
+            .iter_mut()
+            .for_each(|hist| hist.iter_mut().for_each(|val| *val = T::zero()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ScalarInterpolator, TruePeakMeter, WindowFunction};
+
+    fn sine(len: usize, omega: f64, phase: f64) -> Vec<f64> {
+        (0..len).map(|n| (omega * n as f64 + phase).sin()).collect()
+    }
+
+    #[test]
+    fn quarter_rate_sine() {
+        // a sine at fs/4 with 45 degrees phase has all samples at +-0.707,
+        // while the true peak is 1.0
+        let wave = sine(1024, std::f64::consts::PI / 2.0, std::f64::consts::PI / 4.0);
+        let sample_peak = wave.iter().fold(0.0f64, |acc, val| acc.max(val.abs()));
+        assert!((20.0 * sample_peak.log10() + 3.01).abs() < 0.01);
+        let mut meter = TruePeakMeter::<f64>::new(1);
+        for chunk in wave.chunks(100) {
+            meter.process(&[chunk]).unwrap();
+        }
+        let dbtp = meter.true_peak_dbtp()[0];
+        assert!(dbtp.abs() < 0.2, "got {} dBTP", dbtp);
+    }
+
+    #[test]
+    fn custom_interpolator() {
+        let interpolator = Box::new(ScalarInterpolator::<f64>::new(
+            64,
+            16,
+            0.95,
+            WindowFunction::BlackmanHarris2,
+        ));
+        let mut meter = TruePeakMeter::new_with_interpolator(interpolator, 2);
+        let wave = sine(1000, 0.5, 0.3);
+        let silence = vec![0.0; 1000];
+        meter.process(&[&wave, &silence]).unwrap();
+        let peaks = meter.true_peak();
+        assert!((peaks[0] - 1.0).abs() < 0.01, "got {}", peaks[0]);
+        assert_eq!(peaks[1], 0.0);
+        assert!(meter.true_peak_dbtp()[1].is_infinite());
+    }
+
+    #[test]
+    fn skipped_channel_and_reset() {
+        let mut meter = TruePeakMeter::<f32>::new(2);
+        let wave = vec![0.5; 64];
+        meter.process(&[wave.clone(), Vec::new()]).unwrap();
+        meter.process(&[Vec::new(), wave]).unwrap();
+        let peaks = meter.true_peak();
+        // the channels have separate histories and get the same reading
+        assert_eq!(peaks[0], peaks[1]);
+        assert!(peaks[0] >= 0.5 && peaks[0] < 0.6);
+        meter.reset();
+        assert_eq!(meter.true_peak(), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn wrong_number_of_channels() {
+        let mut meter = TruePeakMeter::<f64>::new(2);
+        let waves = vec![vec![0.0; 64]; 3];
+        assert!(meter.process(&waves).is_err());
+    }
+}
@@ -0,0 +1,508 @@
+use crate::offline::resample_all;
+use crate::{Sample, SampleFormat, VecResampler};
+use num_traits::ToPrimitive;
+use std::convert::TryFrom;
+use std::io;
+use std::io::{Read, Write};
+
+/// The format code of integer PCM data.
+const WAVE_FORMAT_PCM: u16 = 1;
+/// The format code of floating point data.
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+/// The format code of the extensible format, where the actual format is given by a sub format GUID.
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+/// The end of the sub format GUIDs, following the two bytes with the format code.
+const SUBFORMAT_GUID_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// The properties of the audio in a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WavSpec {
+    /// Number of channels.
+    pub channels: usize,
+    /// Sample rate in Hz.
+    pub sample_rate: usize,
+    /// Sample format.
+    pub format: SampleFormat,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// Parse the contents of a "fmt " chunk.
+fn parse_fmt(chunk: &[u8]) -> io::Result<WavSpec> {
+    if chunk.len() < 16 {
+        return Err(invalid_data("The fmt chunk is too short"));
+    }
+    let mut format_code = read_u16(chunk, 0);
+    let channels = read_u16(chunk, 2) as usize;
+    let sample_rate = read_u32(chunk, 4) as usize;
+    let bits = read_u16(chunk, 14);
+    if format_code == WAVE_FORMAT_EXTENSIBLE {
+        if chunk.len() < 40 {
+            return Err(invalid_data("The extensible fmt chunk is too short"));
+        }
+        format_code = read_u16(chunk, 24);
+        if chunk[26..40] != SUBFORMAT_GUID_TAIL {
+            return Err(invalid_data("Unsupported sub format"));
+        }
+    }
+    let format = match (format_code, bits) {
+        (WAVE_FORMAT_PCM, 16) => SampleFormat::S16LE,
+        (WAVE_FORMAT_PCM, 24) => SampleFormat::S24LE,
+        (WAVE_FORMAT_PCM, 32) => SampleFormat::S32LE,
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => SampleFormat::F32LE,
+        (WAVE_FORMAT_IEEE_FLOAT, 64) => SampleFormat::F64LE,
+        _ => return Err(invalid_data("Unsupported sample format")),
+    };
+    if channels == 0 || sample_rate == 0 {
+        return Err(invalid_data("Invalid number of channels or sample rate"));
+    }
+    Ok(WavSpec {
+        channels,
+        sample_rate,
+        format,
+    })
+}
+
+/// Read a complete WAV file.
+///
+/// Both the plain PCM and float formats, and WAVE_FORMAT_EXTENSIBLE are supported,
+/// with 16, 24 and 32 bit integer, and 32 and 64 bit float samples.
+/// Chunks other than "fmt " and "data" are skipped.
+/// The data chunk is read at most to the end of the input, which also handles files
+/// that were streamed with a placeholder size.
+/// Returns the properties of the file, and the samples with one vector per channel.
+/// # Errors
+///
+/// The function returns an error if reading fails, or if the file is not a supported WAV file.
+pub fn read_wav<T, R>(mut reader: R) -> io::Result<(WavSpec, Vec<Vec<T>>)>
+where
+    T: Sample,
+    R: Read,
+{
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(invalid_data("Not a RIFF WAVE file"));
+    }
+    let mut spec = None;
+    loop {
+        let mut chunk_header = [0u8; 8];
+        reader.read_exact(&mut chunk_header)?;
+        let chunk_size = read_u32(&chunk_header, 4);
+        match &chunk_header[0..4] {
+            b"fmt " => {
+                let mut chunk = Vec::new();
+                (&mut reader)
+                    .take(chunk_size as u64)
+                    .read_to_end(&mut chunk)?;
+                if chunk.len() != chunk_size as usize {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "The fmt chunk is truncated",
+                    ));
+                }
+                spec = Some(parse_fmt(&chunk)?);
+            }
+            b"data" => {
+                let spec = spec.ok_or_else(|| invalid_data("The data chunk is before fmt"))?;
+                // The size can't be trusted, streamed files are often written with a placeholder.
+                // Read at most to the end of the file, and to the end when the size is unknown.
+                let mut chunk = Vec::new();
+                if chunk_size == u32::MAX {
+                    reader.read_to_end(&mut chunk)?;
+                } else {
+                    reader.take(chunk_size as u64).read_to_end(&mut chunk)?;
+                }
+                let bytes_per_sample = spec.format.bytes_per_sample();
+                let frames = chunk.len() / (bytes_per_sample * spec.channels);
+                debug!("Read WAV file, {:?}, {} frames", spec, frames);
+                let mut waves = vec![Vec::with_capacity(frames); spec.channels];
+                for frame in chunk.chunks_exact(bytes_per_sample * spec.channels) {
+                    for (wave, bytes) in waves.iter_mut().zip(frame.chunks(bytes_per_sample)) {
+                        wave.push(T::coerce(spec.format.decode(bytes)));
+                    }
+                }
+                return Ok((spec, waves));
+            }
+            _ => {
+                trace!(
+                    "Skipping chunk {:?}",
+                    String::from_utf8_lossy(&chunk_header[0..4])
+                );
+                io::copy(&mut (&mut reader).take(chunk_size as u64), &mut io::sink())?;
+            }
+        }
+        if chunk_size % 2 == 1 {
+            // chunks are padded to an even length, the pad byte may be missing at the end of the file
+            let mut pad = [0u8; 1];
+            let _ = reader.read(&mut pad)?;
+        }
+    }
+}
+
+/// Get the sizes of the RIFF and data chunks of a WAV file,
+/// or an error if the file would be too large for the 32 bit sizes.
+fn chunk_sizes(frames: usize, block_align: usize, fmt_len: usize) -> io::Result<(u32, u32)> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "Too much data for a WAV file");
+    let data_size = frames.checked_mul(block_align).ok_or_else(too_large)?;
+    let riff_size = (4 + 8 + fmt_len + 8 + data_size % 2)
+        .checked_add(data_size)
+        .ok_or_else(too_large)?;
+    if riff_size > u32::MAX as usize {
+        return Err(too_large());
+    }
+    Ok((riff_size as u32, data_size as u32))
+}
+
+/// Write a complete WAV file.
+///
+/// Files with more than two channels are written using WAVE_FORMAT_EXTENSIBLE,
+/// with no speaker positions given. Other files use the plain PCM or float formats.
+/// # Errors
+///
+/// The function returns an error if writing fails, if the number of channels does not match
+/// the properties, if the channels have different lengths, or if there is too much data
+/// for the 32 bit sizes of the WAV format.
+pub fn write_wav<T, W>(mut writer: W, spec: &WavSpec, waves: &[Vec<T>]) -> io::Result<()>
+where
+    T: Sample + ToPrimitive,
+    W: Write,
+{
+    if waves.len() != spec.channels {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Wrong number of channels",
+        ));
+    }
+    let frames = waves.first().map(|wave| wave.len()).unwrap_or(0);
+    if waves.iter().any(|wave| wave.len() != frames) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The channels have different lengths",
+        ));
+    }
+    if spec.channels > u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Too many channels for a WAV file",
+        ));
+    }
+    let bytes_per_sample = spec.format.bytes_per_sample();
+    let format_code = match spec.format {
+        SampleFormat::F32LE | SampleFormat::F64LE => WAVE_FORMAT_IEEE_FLOAT,
+        _ => WAVE_FORMAT_PCM,
+    };
+    let block_align = bytes_per_sample * spec.channels;
+    let invalid = |what| io::Error::new(io::ErrorKind::InvalidInput, what);
+    let sample_rate = u32::try_from(spec.sample_rate)
+        .map_err(|_| invalid("The sample rate is too high for a WAV file"))?;
+    let block_align_u16 =
+        u16::try_from(block_align).map_err(|_| invalid("Too many channels for a WAV file"))?;
+    let byte_rate = spec
+        .sample_rate
+        .checked_mul(block_align)
+        .and_then(|rate| u32::try_from(rate).ok())
+        .ok_or_else(|| invalid("The byte rate is too high for a WAV file"))?;
+    let mut fmt = Vec::with_capacity(40);
+    // WAVE_FORMAT_EXTENSIBLE is required for more than two channels or more than 16 bits
+    let extensible = spec.channels > 2 || bytes_per_sample > 2;
+    fmt.extend_from_slice(
+        &if extensible {
+            WAVE_FORMAT_EXTENSIBLE
+        } else {
+            format_code
+        }
+        .to_le_bytes(),
+    );
+    fmt.extend_from_slice(&(spec.channels as u16).to_le_bytes());
+    fmt.extend_from_slice(&sample_rate.to_le_bytes());
+    fmt.extend_from_slice(&byte_rate.to_le_bytes());
+    fmt.extend_from_slice(&block_align_u16.to_le_bytes());
+    fmt.extend_from_slice(&(8 * bytes_per_sample as u16).to_le_bytes());
+    if extensible {
+        fmt.extend_from_slice(&22u16.to_le_bytes());
+        fmt.extend_from_slice(&(8 * bytes_per_sample as u16).to_le_bytes());
+        fmt.extend_from_slice(&0u32.to_le_bytes());
+        fmt.extend_from_slice(&format_code.to_le_bytes());
+        fmt.extend_from_slice(&SUBFORMAT_GUID_TAIL);
+    }
+
+    let (riff_size, data_size) = chunk_sizes(frames, block_align, fmt.len())?;
+    let padding = data_size as usize % 2;
+    debug!("Write WAV file, {:?}, {} frames", spec, frames);
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&riff_size.to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&(fmt.len() as u32).to_le_bytes())?;
+    writer.write_all(&fmt)?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+    let mut data = vec![0u8; data_size as usize + padding];
+    for (n, frame) in data.chunks_exact_mut(block_align).enumerate() {
+        for (wave, bytes) in waves.iter().zip(frame.chunks_mut(bytes_per_sample)) {
+            spec.format.encode(wave[n].to_f64().unwrap_or(0.0), bytes);
+        }
+    }
+    writer.write_all(&data)?;
+    writer.flush()
+}
+
+/// Resample a complete WAV file.
+///
+/// The file is read, resampled to `fs_out` and written with the same sample format and number of channels.
+/// The closure `make_resampler` is called with the properties of the input file,
+/// and should return a resampler for converting from its sample rate to `fs_out`.
+/// The delay of the resampler is removed, and the output has exactly
+/// `round(frames_in * fs_out / fs_in)` frames, so that the end of the file is not lost.
+/// Returns the properties of the input file.
+/// # Errors
+///
+/// The function returns an error if reading or writing fails, if the input is not a supported WAV file,
+/// or if the resampler does not match the file.
+pub fn resample_wav<T, R, W, F>(
+    reader: R,
+    writer: W,
+    fs_out: usize,
+    make_resampler: F,
+) -> io::Result<WavSpec>
+where
+    T: Sample + ToPrimitive,
+    R: Read,
+    W: Write,
+    F: FnOnce(&WavSpec) -> Box<dyn VecResampler<T>>,
+{
+    let (spec, waves) = read_wav::<T, R>(reader)?;
+    let mut resampler = make_resampler(&spec);
+    let frames_in = waves.first().map(|wave| wave.len()).unwrap_or(0);
+    let frames_out = (frames_in as f64 * fs_out as f64 / spec.sample_rate as f64).round() as usize;
+    let output = resample_all(resampler.as_mut(), &waves, frames_out)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let spec_out = WavSpec {
+        sample_rate: fs_out,
+        ..spec
+    };
+    write_wav(writer, &spec_out, &output)?;
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::chunk_sizes;
+    use crate::{
+        read_wav, resample_wav, write_wav, FftFixedIn, InterpolationParameters, InterpolationType,
+        SampleFormat, SincFixedOut, WavSpec, WindowFunction,
+    };
+
+    fn roundtrip(channels: usize, format: SampleFormat) {
+        let spec = WavSpec {
+            channels,
+            sample_rate: 44100,
+            format,
+        };
+        let waves: Vec<Vec<f64>> = (0..channels)
+            .map(|chan| {
+                (0..101)
+                    .map(|n| ((n + chan) as f64 * 0.1).sin() * 0.5)
+                    .collect()
+            })
+            .collect();
+        let mut file = Vec::new();
+        write_wav(&mut file, &spec, &waves).unwrap();
+        assert_eq!(file.len() % 2, 0);
+        let extensible = u16::from_le_bytes([file[20], file[21]]) == 0xFFFE;
+        assert_eq!(extensible, channels > 2 || format.bytes_per_sample() > 2);
+        let (spec_read, waves_read) = read_wav::<f64, _>(file.as_slice()).unwrap();
+        assert_eq!(spec_read, spec);
+        for (wave, wave_read) in waves.iter().zip(waves_read.iter()) {
+            assert_eq!(wave_read.len(), 101);
+            for (val, val_read) in wave.iter().zip(wave_read.iter()) {
+                assert!((val - val_read).abs() < 1.0e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_formats() {
+        for format in [
+            SampleFormat::S16LE,
+            SampleFormat::S24LE,
+            SampleFormat::S32LE,
+            SampleFormat::F32LE,
+            SampleFormat::F64LE,
+        ]
+        .iter()
+        {
+            roundtrip(1, *format);
+            roundtrip(2, *format);
+            roundtrip(6, *format);
+        }
+    }
+
+    #[test]
+    fn reject_too_high_rate() {
+        let spec = WavSpec {
+            channels: 8,
+            sample_rate: 100_000_000,
+            format: SampleFormat::F64LE,
+        };
+        let err = write_wav(Vec::new(), &spec, &vec![vec![0.0f64; 10]; 8]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn skip_chunks_and_reject_invalid() {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            format: SampleFormat::S16LE,
+        };
+        let mut file = Vec::new();
+        write_wav(&mut file, &spec, &[vec![0.25f32; 10]]).unwrap();
+        // insert an odd sized LIST chunk before fmt
+        let mut with_list = file[..12].to_vec();
+        with_list.extend_from_slice(b"LIST");
+        with_list.extend_from_slice(&3u32.to_le_bytes());
+        with_list.extend_from_slice(&[1, 2, 3, 0]);
+        with_list.extend_from_slice(&file[12..]);
+        let (spec_read, waves) = read_wav::<f32, _>(with_list.as_slice()).unwrap();
+        assert_eq!(spec_read, spec);
+        assert_eq!(waves[0], vec![0.25; 10]);
+
+        // a LIST chunk with a size past the end of the file
+        let mut truncated = file[..36].to_vec();
+        truncated.extend_from_slice(b"LIST");
+        truncated.extend_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        truncated.extend_from_slice(&[1, 2, 3, 4]);
+        assert!(read_wav::<f32, _>(truncated.as_slice()).is_err());
+
+        let mut bad = file.clone();
+        bad[8..12].copy_from_slice(b"AVI ");
+        assert!(read_wav::<f32, _>(bad.as_slice()).is_err());
+        let mut bad = file;
+        // 8 bit is not supported
+        bad[34] = 8;
+        assert!(read_wav::<f32, _>(bad.as_slice()).is_err());
+    }
+
+    #[test]
+    fn data_size_past_end() {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            format: SampleFormat::S16LE,
+        };
+        let mut file = Vec::new();
+        write_wav(&mut file, &spec, &[vec![0.25f32; 10]]).unwrap();
+        // a streamed file, with a placeholder size
+        file[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        let (_, waves) = read_wav::<f32, _>(file.as_slice()).unwrap();
+        assert_eq!(waves[0], vec![0.25; 10]);
+        // a truncated file, also with an incomplete last frame
+        file[40..44].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        file.push(0);
+        let (_, waves) = read_wav::<f32, _>(file.as_slice()).unwrap();
+        assert_eq!(waves[0], vec![0.25; 10]);
+    }
+
+    #[test]
+    fn too_large_for_wav() {
+        assert_eq!(chunk_sizes(1000, 4, 16).unwrap(), (4036, 4000));
+        assert_eq!(chunk_sizes(1001, 3, 16).unwrap(), (3040, 3003));
+        assert!(chunk_sizes(1 << 30, 4, 16).is_err());
+        assert!(chunk_sizes(usize::MAX, 4, 16).is_err());
+        let spec = WavSpec {
+            channels: 65536,
+            sample_rate: 8000,
+            format: SampleFormat::S16LE,
+        };
+        let mut file = Vec::new();
+        assert!(write_wav(&mut file, &spec, &vec![vec![0.0f32; 1]; 65536]).is_err());
+    }
+
+    fn resample_file(fft: bool) {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            format: SampleFormat::S24LE,
+        };
+        let sine = |len: usize, fs: f64| -> Vec<f64> {
+            (0..len)
+                .map(|n| 0.5 * (2.0 * std::f64::consts::PI * 440.0 * n as f64 / fs).sin())
+                .collect()
+        };
+        let wave = sine(10000, 44100.0);
+        let waves = vec![wave.clone(), wave.iter().map(|v| -v).collect()];
+        let mut file_in = Vec::new();
+        write_wav(&mut file_in, &spec, &waves).unwrap();
+        let mut file_out = Vec::new();
+        let spec_in =
+            resample_wav::<f64, _, _, _>(file_in.as_slice(), &mut file_out, 48000, |spec| {
+                if fft {
+                    Box::new(FftFixedIn::<f64>::new(
+                        spec.sample_rate,
+                        48000,
+                        1024,
+                        2,
+                        spec.channels,
+                    ))
+                } else {
+                    let params = InterpolationParameters {
+                        sinc_len: 128,
+                        f_cutoff: 0.95,
+                        interpolation: InterpolationType::Cubic,
+                        oversampling_factor: 256,
+                        window: WindowFunction::BlackmanHarris2,
+                    };
+                    Box::new(SincFixedOut::<f64>::new(
+                        48000.0 / 44100.0,
+                        params,
+                        1024,
+                        spec.channels,
+                    ))
+                }
+            })
+            .unwrap();
+        assert_eq!(spec_in, spec);
+        let (spec_out, waves_out) = read_wav::<f64, _>(file_out.as_slice()).unwrap();
+        assert_eq!(spec_out.sample_rate, 48000);
+        assert_eq!(spec_out.format, SampleFormat::S24LE);
+        assert_eq!(waves_out[0].len(), 10884);
+        // The delay is removed, and the signal reaches all the way to the end.
+        // Skip the abrupt start and end. A delay error of one frame gives an error of 0.029,
+        // the sinc resampler leaves a fraction of 0.08 frames.
+        let expected = sine(10884, 48000.0);
+        for (n, exp) in expected.iter().enumerate().take(10784).skip(100) {
+            assert!((waves_out[0][n] - exp).abs() < 3.0e-3, "frame {}", n);
+            assert!((waves_out[1][n] + exp).abs() < 3.0e-3, "frame {}", n);
+        }
+    }
+
+    #[test]
+    fn resample_file_sinc() {
+        resample_file(false);
+    }
+
+    #[test]
+    fn resample_file_fft() {
+        resample_file(true);
+    }
+}